@@ -1,5 +1,14 @@
-use clap::{Parser, ColorChoice};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, Parser, Subcommand, ColorChoice};
 use std::path::PathBuf;
+use crate::diff::DiffFormat;
+use crate::encoding::NameEncoding;
+use crate::entry_path::PathLayout;
+use crate::filter::EntryFilter;
+use crate::hash::HashAlgorithm;
+use crate::overwrite::OverwritePolicy;
+use crate::pack::{PackOptions, PackSource, SortOrder};
+use crate::walk::SymlinkPolicy;
 
 /// 获取自定义的clap样式
 pub fn get_styles() -> clap::builder::Styles {
@@ -18,18 +27,23 @@ pub fn get_styles() -> clap::builder::Styles {
     author = "PVZ PAK Tool",
     version,
     about = "PVZ PAK文件操作工具 - 植物大战僵尸资源包管理器",
-    long_about = "一个强大的Plants vs Zombies PAK文件操作工具，支持打包和解包操作。\n\n支持的操作：\n  • 解包 .pak 文件到目录\n  • 将目录打包为 .pak 文件\n  • 交互式文件浏览器（REPL模式）\n  • 批处理命令执行（-c 模式）",
+    long_about = "一个强大的Plants vs Zombies PAK文件操作工具，支持打包和解包操作。\n\n支持的操作：\n  • 解包 PAK 文件到目录\n  • 将目录打包为 PAK 文件\n  • 交互式文件浏览器（REPL模式）\n  • 批处理命令执行（-c 模式）\n\n既可以使用子命令（pkt unpack、pkt pack ...），也可以直接给出输入路径：\n目录将被打包，PAK文件（按文件头识别，不看扩展名）将被解包或进入交互模式。",
     color = ColorChoice::Auto,
-    styles = get_styles()
+    styles = get_styles(),
+    args_conflicts_with_subcommands = true
 )]
-#[command(override_usage = "pkt.exe <INPUT> [--output <o>]")]
+#[command(override_usage = "pkt <COMMAND> [OPTIONS]\n       pkt <INPUT> [--output <OUTPUT>] [OPTIONS]")]
 pub struct Cli {
-    /// 输入文件或目录 (.pak文件将被解包，目录将被打包)
+    /// 子命令
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    
+    /// 输入文件或目录（目录将被打包，PAK文件将被解包）
     #[arg(
         value_name = "INPUT",
         help = "输入文件或目录路径"
     )]
-    pub input: PathBuf,
+    pub input: Option<PathBuf>,
     
     /// 输出路径（可选，不提供时进入REPL模式）
     #[arg(
@@ -49,4 +63,533 @@ pub struct Cli {
         action = clap::ArgAction::Append
     )]
     pub commands: Vec<String>,
-} 
\ No newline at end of file
+    
+    #[command(flatten)]
+    pub pack: PackArgs,
+    
+    #[command(flatten)]
+    pub encoding: EncodingArgs,
+    
+    #[command(flatten)]
+    pub overwrite: OverwriteArgs,
+    
+    #[command(flatten)]
+    pub layout: LayoutArgs,
+    
+    /// 解包时写入清单；打包时指定清单路径
+    #[arg(
+        long = "manifest",
+        value_name = "PATH",
+        help = "解包时写入清单（默认为输出目录下的 .pakmanifest）；打包时使用指定的清单（默认自动使用输入目录下的 .pakmanifest）",
+        num_args = 0..=1
+    )]
+    pub manifest: Option<Option<PathBuf>>,
+}
+
+impl Cli {
+    /// 在命令行中实际给出的参数（不含默认值），按 `--long` 的形式返回
+    pub fn given_args(matches: &ArgMatches, ids: &[&str]) -> Vec<String> {
+        let command = Cli::command();
+        ids.iter()
+            .filter(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+            .map(|id| {
+                let arg = command.get_arguments().find(|arg| arg.get_id() == *id);
+                match arg.and_then(|arg| arg.get_long()) {
+                    Some(long) => format!("--{}", long),
+                    None => id.to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// 子命令
+#[derive(Subcommand)]
+pub enum Command {
+    /// 解包PAK文件到目录
+    Unpack {
+        #[arg(value_name = "PAK", help = "要解包的PAK文件")]
+        input: PathBuf,
+        
+        #[arg(short = 'o', long = "output", value_name = "DIR", help = "输出目录")]
+        output: PathBuf,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+        
+        #[arg(
+            long = "manifest",
+            value_name = "PATH",
+            help = "写入清单（默认为输出目录下的 .pakmanifest）",
+            num_args = 0..=1
+        )]
+        manifest: Option<Option<PathBuf>>,
+        
+        #[command(flatten)]
+        filter: FilterArgs,
+        
+        #[command(flatten)]
+        overwrite: OverwriteArgs,
+        
+        #[command(flatten)]
+        layout: LayoutArgs,
+    },
+    
+    /// 将目录打包为PAK文件
+    Pack {
+        #[arg(value_name = "DIR", help = "要打包的目录（使用 --add 时可省略）", required_unless_present = "add")]
+        input: Option<PathBuf>,
+        
+        #[arg(short = 'o', long = "output", value_name = "PAK", help = "输出的PAK文件")]
+        output: PathBuf,
+        
+        #[command(flatten)]
+        pack: PackArgs,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+        
+        #[arg(
+            long = "manifest",
+            value_name = "PATH",
+            help = "使用指定的清单（默认自动使用输入目录下的 .pakmanifest）"
+        )]
+        manifest: Option<PathBuf>,
+        
+        #[arg(long = "force", help = "替换已存在的输出文件")]
+        force: bool,
+    },
+    
+    /// 列出PAK中的文件
+    Ls {
+        #[arg(value_name = "PAK", help = "PAK文件")]
+        pak: PathBuf,
+        
+        #[arg(value_name = "PATH", help = "PAK内的目录（默认为根目录）")]
+        path: Option<String>,
+        
+        #[arg(short = 'l', help = "显示修改时间和大小")]
+        long: bool,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+    },
+    
+    /// 将PAK中的文件内容输出到标准输出
+    Cat {
+        #[arg(value_name = "PAK", help = "PAK文件")]
+        pak: PathBuf,
+        
+        #[arg(value_name = "ENTRY", help = "条目名（不区分大小写，正反斜杠均可）")]
+        entry: String,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+    },
+    
+    /// 显示PAK文件信息
+    Info {
+        #[arg(value_name = "PAK", help = "PAK文件")]
+        pak: PathBuf,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+    },
+    
+    /// 校验PAK文件中的所有条目
+    Verify {
+        #[arg(value_name = "PAK", help = "PAK文件")]
+        pak: PathBuf,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+    },
+    
+    /// 从截断或损坏的PAK中尽量提取条目，并写出恢复报告
+    Salvage {
+        #[arg(value_name = "PAK", help = "损坏的PAK文件")]
+        input: PathBuf,
+        
+        #[arg(short = 'o', long = "output", value_name = "DIR", help = "输出目录")]
+        output: PathBuf,
+        
+        #[arg(long = "report", value_name = "PATH", help = "报告路径（默认为输出目录下的 .paksalvage）")]
+        report: Option<PathBuf>,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+        
+        #[command(flatten)]
+        overwrite: OverwriteArgs,
+    },
+    
+    /// 按 sha256sum 的格式输出每个条目的校验和，或按清单检查PAK或解包目录
+    Hash {
+        #[arg(value_name = "TARGET", help = "PAK文件（--check 时也可以是解包目录）")]
+        target: PathBuf,
+        
+        #[arg(
+            long = "algo",
+            value_name = "ALGO",
+            help = "校验算法: crc32、md5、sha1、sha256（默认 sha256；--check 时默认按校验和的长度识别）",
+            value_parser = str::parse::<HashAlgorithm>
+        )]
+        algo: Option<HashAlgorithm>,
+        
+        #[arg(long = "check", value_name = "MANIFEST", help = "按 sha256sum 格式的清单检查，有不一致时返回非零退出码")]
+        check: Option<PathBuf>,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+    },
+    
+    /// 比较两个PAK文件：新增、删除、修改、移动和只有元数据变化的条目
+    Diff {
+        #[arg(value_name = "OLD", help = "旧的PAK文件")]
+        old: PathBuf,
+        
+        #[arg(value_name = "NEW", help = "新的PAK文件")]
+        new: PathBuf,
+        
+        #[arg(
+            long = "format",
+            value_name = "FORMAT",
+            help = "输出格式: text、json（默认 text）",
+            default_value = "text",
+            value_parser = str::parse::<DiffFormat>
+        )]
+        format: DiffFormat,
+        
+        #[arg(long = "content", help = "为修改过的文本条目（如 XML、LawnStrings.txt）输出统一格式的逐行差异")]
+        content: bool,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+    },
+    
+    /// 制作或应用增量补丁（只包含相对基础PAK新增或修改的条目数据）
+    Patch {
+        #[command(subcommand)]
+        action: PatchCommand,
+    },
+    
+    /// 进入交互模式，或用 -c 执行命令后退出
+    Shell {
+        #[arg(value_name = "PAK", help = "PAK文件")]
+        pak: PathBuf,
+        
+        #[arg(
+            short = 'c',
+            long = "command",
+            value_name = "COMMAND",
+            help = "要执行的命令（可多次使用）",
+            action = clap::ArgAction::Append
+        )]
+        commands: Vec<String>,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+    },
+}
+
+/// 补丁子命令
+#[derive(Subcommand)]
+pub enum PatchCommand {
+    /// 比较基础PAK和修改后的PAK，生成补丁文件
+    Create {
+        #[arg(value_name = "BASE", help = "基础PAK文件（如原版 main.pak）")]
+        base: PathBuf,
+        
+        #[arg(value_name = "MODDED", help = "修改后的PAK文件")]
+        modded: PathBuf,
+        
+        #[arg(short = 'o', long = "output", value_name = "PATCH", help = "输出的补丁文件（如 mod.pakpatch）")]
+        output: PathBuf,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+        
+        #[arg(long = "force", help = "替换已存在的输出文件")]
+        force: bool,
+    },
+    
+    /// 检查基础PAK的校验和后应用补丁，生成修改后的PAK
+    Apply {
+        #[arg(value_name = "BASE", help = "基础PAK文件（必须与制作补丁时使用的完全相同）")]
+        base: PathBuf,
+        
+        #[arg(value_name = "PATCH", help = "补丁文件")]
+        patch: PathBuf,
+        
+        #[arg(short = 'o', long = "output", value_name = "PAK", help = "输出的PAK文件")]
+        output: PathBuf,
+        
+        #[command(flatten)]
+        encoding: EncodingArgs,
+        
+        #[arg(long = "force", help = "替换已存在的输出文件")]
+        force: bool,
+    },
+}
+
+/// 条目筛选参数
+#[derive(Args)]
+pub struct FilterArgs {
+    /// 只包含匹配通配符的条目
+    #[arg(
+        long = "include",
+        value_name = "PATTERN",
+        help = "只处理匹配通配符的条目（如 '*.reanim'，路径用 / 分隔，可多次使用）",
+        action = clap::ArgAction::Append
+    )]
+    pub include: Vec<String>,
+    
+    /// 排除匹配通配符的条目
+    #[arg(
+        long = "exclude",
+        value_name = "PATTERN",
+        help = "排除匹配通配符的条目（如 'images/*'，可多次使用）",
+        action = clap::ArgAction::Append
+    )]
+    pub exclude: Vec<String>,
+    
+    /// 只包含匹配正则表达式的条目
+    #[arg(
+        long = "regex",
+        value_name = "REGEX",
+        help = "只处理匹配正则表达式的条目（可多次使用）",
+        action = clap::ArgAction::Append
+    )]
+    pub regex: Vec<String>,
+    
+    /// 只包含列表文件中的条目
+    #[arg(
+        long = "from-list",
+        value_name = "FILE",
+        help = "只处理列表文件中的条目（每行一个条目名，不区分大小写）"
+    )]
+    pub from_list: Option<PathBuf>,
+}
+
+impl FilterArgs {
+    /// 转换为条目筛选条件
+    pub fn to_filter(&self) -> EntryFilter {
+        EntryFilter {
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            regex: self.regex.clone(),
+            from_list: self.from_list.clone(),
+        }
+    }
+}
+
+/// 覆盖策略参数（互斥）
+#[derive(Args)]
+#[group(multiple = false)]
+pub struct OverwriteArgs {
+    /// 覆盖已存在的文件
+    #[arg(long = "force", help = "解包到已有目录，覆盖已存在的文件；打包时替换已存在的PAK")]
+    pub force: bool,
+    
+    /// 保留已存在的文件
+    #[arg(long = "skip-existing", help = "解包到已有目录，保留已存在的文件")]
+    pub skip_existing: bool,
+    
+    /// 只覆盖有变化的文件
+    #[arg(long = "update", help = "解包到已有目录，只覆盖大小或修改时间不同的文件")]
+    pub update: bool,
+    
+    /// 覆盖前备份
+    #[arg(long = "backup", help = "解包到已有目录，覆盖前把已存在的文件改名为 .bak")]
+    pub backup: bool,
+}
+
+impl OverwriteArgs {
+    /// 所有覆盖策略参数
+    pub const ARG_IDS: &'static [&'static str] = &["force", "skip_existing", "update", "backup"];
+    
+    /// 只用于解包的覆盖策略参数（--force 打包时也可以用）
+    pub const UNPACK_ONLY_ARG_IDS: &'static [&'static str] = &["skip_existing", "update", "backup"];
+    
+    /// 转换为覆盖策略（未指定时为 Refuse）
+    pub fn to_policy(&self) -> OverwritePolicy {
+        if self.force {
+            OverwritePolicy::Force
+        } else if self.skip_existing {
+            OverwritePolicy::SkipExisting
+        } else if self.update {
+            OverwritePolicy::Update
+        } else if self.backup {
+            OverwritePolicy::Backup
+        } else {
+            OverwritePolicy::Refuse
+        }
+    }
+}
+
+/// 解包布局参数
+#[derive(Args)]
+pub struct LayoutArgs {
+    /// 去掉条目路径开头的目录层级
+    #[arg(
+        long = "strip-components",
+        value_name = "N",
+        help = "解包时去掉条目路径开头的 N 层目录，层级不足的条目被跳过"
+    )]
+    pub strip_components: Option<usize>,
+    
+    /// 只保留文件名
+    #[arg(
+        long = "flatten",
+        help = "解包时去掉所有目录，只保留文件名",
+        conflicts_with = "strip_components"
+    )]
+    pub flatten: bool,
+}
+
+impl LayoutArgs {
+    /// 所有解包布局参数
+    pub const ARG_IDS: &'static [&'static str] = &["strip_components", "flatten"];
+    
+    /// 转换为解包布局
+    pub fn to_layout(&self) -> PathLayout {
+        match (self.flatten, self.strip_components) {
+            (true, _) => PathLayout::Flatten,
+            (false, Some(count)) => PathLayout::StripComponents(count),
+            (false, None) => PathLayout::Full,
+        }
+    }
+}
+
+/// 文件名编码参数
+#[derive(Args)]
+pub struct EncodingArgs {
+    /// 文件名编码
+    #[arg(
+        long = "encoding",
+        value_name = "ENCODING",
+        help = "文件名编码: gbk、shift_jis、windows-1252、utf-8、auto（默认 gbk）",
+        default_value = "gbk",
+        value_parser = str::parse::<NameEncoding>
+    )]
+    pub encoding: NameEncoding,
+}
+
+/// 打包参数
+#[derive(Args)]
+pub struct PackArgs {
+    /// 打包时使用zlib压缩（可选压缩等级 0-9，默认 6）
+    #[arg(
+        long = "compress",
+        value_name = "LEVEL",
+        help = "打包时使用zlib压缩，可指定压缩等级 0-9（默认 6）",
+        num_args = 0..=1,
+        default_missing_value = "6",
+        value_parser = clap::value_parser!(u32).range(0..=9)
+    )]
+    pub compress: Option<u32>,
+    
+    /// 打包时所有条目使用固定时间戳（默认使用文件的修改时间）
+    #[arg(
+        long = "fixed-time",
+        value_name = "FILETIME",
+        help = "打包时所有条目使用固定的FILETIME时间戳，不指定值时使用PopCap默认时间戳",
+        num_args = 0..=1,
+        default_missing_value = "129146222018596744"
+    )]
+    pub fixed_time: Option<u64>,
+    
+    /// 打包时忽略清单
+    #[arg(
+        long = "no-manifest",
+        help = "打包时忽略输入目录下的 .pakmanifest"
+    )]
+    pub no_manifest: bool,
+    
+    /// 打包时的条目顺序
+    #[arg(
+        long = "sort",
+        value_name = "ORDER",
+        help = "打包时的条目顺序: name（逐字节，默认）、popcap（不区分大小写）、fs（文件系统顺序，不同机器上可能不同）",
+        value_parser = str::parse::<SortOrder>
+    )]
+    pub sort: Option<SortOrder>,
+    
+    /// 按列表文件排列条目
+    #[arg(
+        long = "sort-list",
+        value_name = "FILE",
+        help = "按列表文件（每行一个条目名）排列条目，未列出的文件按名称追加在末尾",
+        conflicts_with = "sort"
+    )]
+    pub sort_list: Option<PathBuf>,
+    
+    /// 打包时只包含匹配的文件
+    #[arg(
+        long = "include",
+        value_name = "PATTERN",
+        help = "打包时只包含匹配的文件（gitignore 语法，如 '*.reanim' 或 'particles/'，可多次使用）",
+        action = clap::ArgAction::Append
+    )]
+    pub include: Vec<String>,
+    
+    /// 打包时排除匹配的文件
+    #[arg(
+        long = "exclude",
+        value_name = "PATTERN",
+        help = "打包时排除匹配的文件或目录（gitignore 语法，在 .pakignore 之后生效，可多次使用）",
+        action = clap::ArgAction::Append
+    )]
+    pub exclude: Vec<String>,
+    
+    /// 打包时对符号链接的处理方式
+    #[arg(
+        long = "symlinks",
+        value_name = "POLICY",
+        help = "打包时对符号链接的处理: follow（跟随，检测循环，默认）、skip（跳过）、error（报错）",
+        default_value = "follow",
+        value_parser = str::parse::<SymlinkPolicy>
+    )]
+    pub symlinks: SymlinkPolicy,
+    
+    /// 额外的打包来源
+    #[arg(
+        long = "add",
+        value_name = "SRC[=PAK_PREFIX]",
+        help = "添加打包来源（目录或文件，可指定PAK内的目录前缀，可多次使用）；后面的来源覆盖前面的同名条目",
+        action = clap::ArgAction::Append,
+        value_parser = str::parse::<PackSource>
+    )]
+    pub add: Vec<PackSource>,
+}
+
+impl PackArgs {
+    /// 只用于打包的参数
+    pub const ARG_IDS: &'static [&'static str] = &[
+        "compress", "fixed_time", "no_manifest", "sort", "sort_list", "include", "exclude", "symlinks", "add",
+    ];
+    
+    /// 所有打包来源：输入目录（如果有）在前，然后是 --add 指定的来源
+    pub fn sources(&self, input: Option<PathBuf>) -> Vec<PackSource> {
+        input.map(PackSource::new).into_iter().chain(self.add.iter().cloned()).collect()
+    }
+    
+    /// 转换为打包选项（编码、清单和是否替换输出由调用方补充）
+    pub fn to_options(&self, encoding: NameEncoding, manifest: Option<PathBuf>) -> PackOptions {
+        PackOptions {
+            compress_level: self.compress,
+            file_time: self.fixed_time,
+            encoding,
+            manifest,
+            ignore_manifest: self.no_manifest,
+            sort: match &self.sort_list {
+                Some(list_path) => SortOrder::List(list_path.clone()),
+                None => self.sort.clone().unwrap_or_default(),
+            },
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            symlinks: self.symlinks,
+            force: false,
+        }
+    }
+}
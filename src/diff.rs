@@ -0,0 +1,495 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Seek};
+use std::path::Path;
+use std::str::FromStr;
+use colored::*;
+use similar::TextDiff;
+use crate::encoding::NameEncoding;
+use crate::hash::{hash_entry, HashAlgorithm};
+use crate::reader::{normalize_entry_name, PakReader};
+use crate::utils::format_filetime;
+
+/// 条目的变化类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// 只存在于新PAK
+    Added,
+    /// 只存在于旧PAK
+    Removed,
+    /// 内容不同
+    Modified,
+    /// 内容相同，路径改变
+    Moved,
+    /// 内容相同，只有修改时间或相对顺序改变
+    Metadata,
+}
+
+impl ChangeKind {
+    /// 文本输出中的标记
+    fn symbol(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "+",
+            ChangeKind::Removed => "-",
+            ChangeKind::Modified => "M",
+            ChangeKind::Moved => "R",
+            ChangeKind::Metadata => "~",
+        }
+    }
+
+    /// JSON 输出中的名称
+    fn json_name(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Moved => "moved",
+            ChangeKind::Metadata => "metadata",
+        }
+    }
+}
+
+/// 条目在一个PAK中的位置和元数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryState {
+    /// 在索引中的序号
+    pub index: usize,
+    /// 内容大小（压缩模式下为原始大小）
+    pub size: u64,
+    /// FILETIME 时间戳
+    pub file_time: u64,
+}
+
+/// 一个条目的变化
+#[derive(Debug, Clone)]
+pub struct EntryChange {
+    pub kind: ChangeKind,
+    /// 条目名（删除的条目为旧名，其余为新名）
+    pub name: String,
+    /// 移动前的条目名（仅 Moved）
+    pub old_name: Option<String>,
+    /// 旧PAK中的状态（新增的条目为 None）
+    pub old: Option<EntryState>,
+    /// 新PAK中的状态（删除的条目为 None）
+    pub new: Option<EntryState>,
+    /// 与其他未修改的条目相比，相对顺序是否改变
+    pub reordered: bool,
+}
+
+impl EntryChange {
+    /// 修改时间是否改变
+    pub fn time_changed(&self) -> bool {
+        matches!((self.old, self.new), (Some(old), Some(new)) if old.file_time != new.file_time)
+    }
+}
+
+/// diff 的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for DiffFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(DiffFormat::Text),
+            "json" => Ok(DiffFormat::Json),
+            _ => Err(format!("不支持的输出格式: {}（可选 text、json）", s)),
+        }
+    }
+}
+
+/// diff 选项
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// 文件名编码（Auto 时自动识别），也用于解码文本条目
+    pub encoding: NameEncoding,
+    pub format: DiffFormat,
+    /// 为修改过的文本条目输出统一格式的逐行差异
+    pub content: bool,
+}
+
+/// 比较两个PAK的条目（按名称匹配，不区分大小写）
+///
+/// 名称相同的条目按内容的大小和 SHA-256 比较；只存在于一侧的条目再按内容配对，识别为移动。
+/// 内容相同的条目还会比较修改时间，以及与其他条目的相对顺序（不在最长的保持顺序的序列中即视为顺序改变）。
+/// 结果先按新PAK的顺序列出新增、修改、移动和元数据变化的条目，再按旧PAK的顺序列出删除的条目。
+pub fn diff_paks<A: Read + Seek, B: Read + Seek>(
+    old: &mut PakReader<A>,
+    new: &mut PakReader<B>,
+) -> io::Result<Vec<EntryChange>> {
+    let mut old_index: HashMap<String, usize> = HashMap::new();
+    for (index, file_info) in old.files().iter().enumerate() {
+        old_index.entry(normalize_entry_name(&file_info.file_name)).or_insert(index);
+    }
+
+    let old_states: Vec<EntryState> = (0..old.files().len()).map(|index| entry_state(old, index)).collect();
+    let new_states: Vec<EntryState> = (0..new.files().len()).map(|index| entry_state(new, index)).collect();
+    let mut old_matched = vec![false; old_states.len()];
+    let mut modified = HashMap::new();
+    let mut unchanged = Vec::new();
+    let mut added = Vec::new();
+
+    for (new_index, new_state) in new_states.iter().enumerate() {
+        let name = normalize_entry_name(&new.files()[new_index].file_name);
+        match old_index.get(&name).copied().filter(|&index| !old_matched[index]) {
+            Some(old_index) => {
+                old_matched[old_index] = true;
+                if same_content(old, old_states[old_index], new, *new_state)? {
+                    unchanged.push((new_index, old_index));
+                } else {
+                    modified.insert(new_index, old_index);
+                }
+            }
+            None => added.push(new_index),
+        }
+    }
+
+    // 只存在于一侧、内容相同的条目视为移动（同样的内容出现多次时按顺序一一配对）
+    let mut removed_by_content: HashMap<(u64, String), VecDeque<usize>> = HashMap::new();
+    for old_index in (0..old_states.len()).filter(|&index| !old_matched[index]) {
+        let hash = hash_entry(old, old_index, HashAlgorithm::Sha256)?;
+        removed_by_content.entry((old_states[old_index].size, hash)).or_default().push_back(old_index);
+    }
+    let mut moved = HashMap::new();
+    for &new_index in &added {
+        let hash = hash_entry(new, new_index, HashAlgorithm::Sha256)?;
+        if let Some(old_index) = removed_by_content.get_mut(&(new_states[new_index].size, hash)).and_then(|queue| queue.pop_front()) {
+            old_matched[old_index] = true;
+            moved.insert(new_index, old_index);
+        }
+    }
+
+    // 未修改的条目中，不属于最长的保持旧顺序的序列的条目被调整了顺序
+    let in_order = longest_increasing(&unchanged.iter().map(|&(_, old_index)| old_index).collect::<Vec<_>>());
+    let unchanged: HashMap<usize, (usize, bool)> = unchanged.iter().zip(in_order)
+        .map(|(&(new_index, old_index), kept)| (new_index, (old_index, !kept)))
+        .collect();
+
+    let mut changes = Vec::new();
+    for (new_index, new_state) in new_states.iter().enumerate() {
+        let name = new.files()[new_index].file_name.clone();
+        let change = if let Some(&old_index) = modified.get(&new_index) {
+            EntryChange { kind: ChangeKind::Modified, name, old_name: None, old: Some(old_states[old_index]), new: Some(*new_state), reordered: false }
+        } else if let Some(&old_index) = moved.get(&new_index) {
+            let old_name = Some(old.files()[old_index].file_name.clone());
+            EntryChange { kind: ChangeKind::Moved, name, old_name, old: Some(old_states[old_index]), new: Some(*new_state), reordered: false }
+        } else if let Some(&(old_index, reordered)) = unchanged.get(&new_index) {
+            if !reordered && old_states[old_index].file_time == new_state.file_time {
+                continue;
+            }
+            EntryChange { kind: ChangeKind::Metadata, name, old_name: None, old: Some(old_states[old_index]), new: Some(*new_state), reordered }
+        } else {
+            EntryChange { kind: ChangeKind::Added, name, old_name: None, old: None, new: Some(*new_state), reordered: false }
+        };
+        changes.push(change);
+    }
+
+    for (index, file_info) in old.files().iter().enumerate() {
+        if !old_matched[index] {
+            changes.push(EntryChange {
+                kind: ChangeKind::Removed,
+                name: file_info.file_name.clone(),
+                old_name: None,
+                old: Some(old_states[index]),
+                new: None,
+                reordered: false,
+            });
+        }
+    }
+
+    Ok(changes)
+}
+
+/// 比较两个PAK文件并输出结果，返回两者是否没有任何差异
+pub fn run_diff(old_path: &Path, new_path: &Path, options: &DiffOptions) -> io::Result<bool> {
+    let mut old = PakReader::open_with_encoding(old_path, options.encoding)?;
+    let mut new = PakReader::open_with_encoding(new_path, options.encoding)?;
+    let changes = diff_paks(&mut old, &mut new)?;
+
+    // 只为修改过的文本条目生成逐行差异
+    let mut content_diffs = HashMap::new();
+    if options.content {
+        for (position, change) in changes.iter().enumerate() {
+            if let (ChangeKind::Modified, Some(old_state), Some(new_state)) = (change.kind, change.old, change.new) {
+                let old_data = old.read_entry(old_state.index)?;
+                let new_data = new.read_entry(new_state.index)?;
+                if let Some(diff) = text_diff(&old_data, &new_data, &change.name, old.encoding(), new.encoding()) {
+                    content_diffs.insert(position, diff);
+                }
+            }
+        }
+    }
+
+    match options.format {
+        DiffFormat::Text => print_text(&changes, &content_diffs),
+        DiffFormat::Json => print_json(old_path, new_path, &changes, &content_diffs),
+    }
+    Ok(changes.is_empty())
+}
+
+fn print_text(changes: &[EntryChange], content_diffs: &HashMap<usize, String>) {
+    for (position, change) in changes.iter().enumerate() {
+        let line = format!("{} {}", change.kind.symbol(), describe_change(change));
+        let line = match change.kind {
+            ChangeKind::Added => line.green(),
+            ChangeKind::Removed => line.red(),
+            ChangeKind::Modified => line.yellow(),
+            ChangeKind::Moved => line.cyan(),
+            ChangeKind::Metadata => line.normal(),
+        };
+        println!("{}", line);
+
+        if let Some(diff) = content_diffs.get(&position) {
+            for diff_line in diff.lines() {
+                let colored_line = if diff_line.starts_with("+++") || diff_line.starts_with("---") {
+                    diff_line.bold()
+                } else if diff_line.starts_with('+') {
+                    diff_line.green()
+                } else if diff_line.starts_with('-') {
+                    diff_line.red()
+                } else if diff_line.starts_with("@@") {
+                    diff_line.cyan()
+                } else {
+                    diff_line.normal()
+                };
+                println!("{}", colored_line);
+            }
+        }
+    }
+
+    let count = |kind: ChangeKind| changes.iter().filter(|change| change.kind == kind).count();
+    println!(
+        "新增 {}，删除 {}，修改 {}，移动 {}，仅元数据 {}",
+        count(ChangeKind::Added),
+        count(ChangeKind::Removed),
+        count(ChangeKind::Modified),
+        count(ChangeKind::Moved),
+        count(ChangeKind::Metadata)
+    );
+}
+
+/// 文本输出中一个变化的描述
+fn describe_change(change: &EntryChange) -> String {
+    let mut details = Vec::new();
+    if change.kind == ChangeKind::Modified {
+        if let (Some(old), Some(new)) = (change.old, change.new) {
+            details.push(format!("{} -> {} 字节", old.size, new.size));
+        }
+    }
+    if change.kind != ChangeKind::Added && change.kind != ChangeKind::Removed && change.time_changed() {
+        if let (Some(old), Some(new)) = (change.old, change.new) {
+            details.push(format!("时间 {} -> {}", format_filetime(old.file_time), format_filetime(new.file_time)));
+        }
+    }
+    if change.reordered {
+        if let (Some(old), Some(new)) = (change.old, change.new) {
+            details.push(format!("顺序 #{} -> #{}", old.index + 1, new.index + 1));
+        }
+    }
+
+    let name = match &change.old_name {
+        Some(old_name) => format!("{} -> {}", old_name, change.name),
+        None => change.name.clone(),
+    };
+    if details.is_empty() {
+        name
+    } else {
+        format!("{}（{}）", name, details.join("，"))
+    }
+}
+
+fn print_json(old_path: &Path, new_path: &Path, changes: &[EntryChange], content_diffs: &HashMap<usize, String>) {
+    let count = |kind: ChangeKind| changes.iter().filter(|change| change.kind == kind).count();
+    println!("{{");
+    println!("  \"old\": {},", json_string(&old_path.display().to_string()));
+    println!("  \"new\": {},", json_string(&new_path.display().to_string()));
+    println!(
+        "  \"summary\": {{\"added\": {}, \"removed\": {}, \"modified\": {}, \"moved\": {}, \"metadata\": {}}},",
+        count(ChangeKind::Added),
+        count(ChangeKind::Removed),
+        count(ChangeKind::Modified),
+        count(ChangeKind::Moved),
+        count(ChangeKind::Metadata)
+    );
+    println!("  \"changes\": [");
+    for (position, change) in changes.iter().enumerate() {
+        let mut fields = vec![
+            format!("\"kind\": {}", json_string(change.kind.json_name())),
+            format!("\"name\": {}", json_string(&change.name)),
+        ];
+        if let Some(old_name) = &change.old_name {
+            fields.push(format!("\"old_name\": {}", json_string(old_name)));
+        }
+        for (prefix, state) in [("old", change.old), ("new", change.new)] {
+            if let Some(state) = state {
+                fields.push(format!("\"{}_index\": {}", prefix, state.index));
+                fields.push(format!("\"{}_size\": {}", prefix, state.size));
+                fields.push(format!("\"{}_time\": {}", prefix, json_string(&format_filetime(state.file_time))));
+            }
+        }
+        if change.kind == ChangeKind::Metadata {
+            fields.push(format!("\"time_changed\": {}", change.time_changed()));
+            fields.push(format!("\"reordered\": {}", change.reordered));
+        }
+        if let Some(diff) = content_diffs.get(&position) {
+            fields.push(format!("\"diff\": {}", json_string(diff)));
+        }
+        let separator = if position + 1 < changes.len() { "," } else { "" };
+        println!("    {{{}}}{}", fields.join(", "), separator);
+    }
+    println!("  ]");
+    println!("}}");
+}
+
+/// 条目的状态（压缩模式下的大小为原始大小）
+fn entry_state<R: Read + Seek>(reader: &PakReader<R>, index: usize) -> EntryState {
+    let file_info = &reader.files()[index];
+    let size = if reader.is_compressed() { file_info._size } else { file_info.z_size };
+    EntryState { index, size: size as u64, file_time: file_info._file_time }
+}
+
+/// 比较两个条目的内容（先比较大小，再比较 SHA-256）
+fn same_content<A: Read + Seek, B: Read + Seek>(
+    old: &mut PakReader<A>,
+    old_state: EntryState,
+    new: &mut PakReader<B>,
+    new_state: EntryState,
+) -> io::Result<bool> {
+    if old_state.size != new_state.size {
+        return Ok(false);
+    }
+    Ok(hash_entry(old, old_state.index, HashAlgorithm::Sha256)? == hash_entry(new, new_state.index, HashAlgorithm::Sha256)?)
+}
+
+/// 两个版本都是文本时生成统一格式的逐行差异
+fn text_diff(old_data: &[u8], new_data: &[u8], name: &str, old_encoding: NameEncoding, new_encoding: NameEncoding) -> Option<String> {
+    let old_text = decode_text(old_data, old_encoding)?;
+    let new_text = decode_text(new_data, new_encoding)?;
+    let unix_name = name.replace('\\', "/");
+    let diff = TextDiff::from_lines(&old_text, &new_text)
+        .unified_diff()
+        .header(&format!("a/{}", unix_name), &format!("b/{}", unix_name))
+        .to_string();
+    Some(diff)
+}
+
+/// 把条目内容解码为文本：不含 NUL 字节，并且是有效的 UTF-8 或能按文件名编码无损解码（如GBK的 LawnStrings.txt）
+fn decode_text(data: &[u8], encoding: NameEncoding) -> Option<String> {
+    if data.contains(&0) {
+        return None;
+    }
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    match std::str::from_utf8(data) {
+        Ok(text) => Some(text.to_string()),
+        Err(_) if encoding.decodes_cleanly(data) => Some(encoding.decode(data)),
+        Err(_) => None,
+    }
+}
+
+/// 返回每个元素是否属于最长严格递增子序列
+fn longest_increasing(values: &[usize]) -> Vec<bool> {
+    // tails[k] 为长度 k+1 的递增子序列中末尾最小的元素位置
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; values.len()];
+    for (position, &value) in values.iter().enumerate() {
+        let length = tails.partition_point(|&tail| values[tail] < value);
+        previous[position] = length.checked_sub(1).map(|k| tails[k]);
+        if length == tails.len() {
+            tails.push(position);
+        } else {
+            tails[length] = position;
+        }
+    }
+
+    let mut kept = vec![false; values.len()];
+    let mut current = tails.last().copied();
+    while let Some(position) = current {
+        kept[position] = true;
+        current = previous[position];
+    }
+    kept
+}
+
+/// 转换为 JSON 字符串字面量
+fn json_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::writer::PakWriter;
+
+    fn open_pak(entries: &[(&str, &[u8])]) -> PakReader<Cursor<Vec<u8>>> {
+        let mut writer = PakWriter::new();
+        for (name, data) in entries {
+            writer.add_bytes(name, data.to_vec()).unwrap();
+        }
+        let mut bytes = Vec::new();
+        writer.write_to(&mut bytes).unwrap();
+        PakReader::new(Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn longest_increasing_keeps_one_longest_run() {
+        assert_eq!(longest_increasing(&[]), Vec::<bool>::new());
+        assert_eq!(longest_increasing(&[0, 1, 2]), vec![true, true, true]);
+        assert_eq!(longest_increasing(&[2, 0, 1]), vec![false, true, true]);
+        assert_eq!(longest_increasing(&[0, 3, 1, 2, 4]), vec![true, false, true, true, true]);
+        assert_eq!(longest_increasing(&[1, 0]).iter().filter(|&&kept| kept).count(), 1);
+    }
+
+    #[test]
+    fn diff_classifies_each_kind_of_change() {
+        let mut old = open_pak(&[("a.txt", b"a"), ("b.txt", b"b"), ("c.txt", b"c"), ("gone.txt", b"gone")]);
+        let mut new = open_pak(&[("b.txt", b"b"), ("A.TXT", b"a"), ("c.txt", b"changed"), ("d\\moved.txt", b"gone"), ("e.txt", b"new")]);
+        let changes = diff_paks(&mut old, &mut new).unwrap();
+
+        let kinds: Vec<_> = changes.iter().map(|change| (change.kind, change.name.as_str())).collect();
+        assert_eq!(kinds, vec![
+            (ChangeKind::Metadata, "b.txt"),
+            (ChangeKind::Modified, "c.txt"),
+            (ChangeKind::Moved, "d\\moved.txt"),
+            (ChangeKind::Added, "e.txt"),
+        ]);
+        assert!(changes[0].reordered);
+        assert_eq!(changes[2].old_name.as_deref(), Some("gone.txt"));
+    }
+
+    #[test]
+    fn identical_paks_have_no_changes() {
+        let entries: &[(&str, &[u8])] = &[("a.txt", b"a"), ("b.txt", b"b")];
+        assert!(diff_paks(&mut open_pak(entries), &mut open_pak(entries)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn binary_content_is_not_diffed_as_text() {
+        assert_eq!(decode_text(b"\xEF\xBB\xBFline\n", NameEncoding::Gbk).as_deref(), Some("line\n"));
+        assert_eq!(decode_text(b"a\0b", NameEncoding::Gbk), None);
+        assert_eq!(decode_text(&[0xC4, 0xE3], NameEncoding::Gbk).as_deref(), Some("\u{4f60}"));
+    }
+
+    #[test]
+    fn json_strings_escape_control_characters() {
+        assert_eq!(json_string("a\"b\\c\n\u{1}"), "\"a\\\"b\\\\c\\n\\u0001\"");
+    }
+}
@@ -0,0 +1,274 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use crate::encoding::NameEncoding;
+use crate::error::PakError;
+use crate::pak::{write_pak_info, FileInfo, PakInfo};
+use crate::reader::{normalize_entry_name, PakReader};
+use crate::utils::{compress_data, write_file_atomically};
+use crate::writer::{checked_size, CryptWriter};
+
+/// 压缩模式PAK中新条目的默认压缩等级
+const DEFAULT_COMPRESS_LEVEL: u32 = 6;
+
+/// 编辑后的条目
+enum EditEntry {
+    /// 原PAK中的条目（数据原样复制，可能被重命名）
+    Original { index: usize, name: String },
+    /// 新增或替换的条目
+    New { name: String, source: NewSource, file_time: u64 },
+}
+
+/// 新条目的数据来源
+enum NewSource {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+impl EditEntry {
+    fn name(&self) -> &str {
+        match self {
+            EditEntry::Original { name, .. } | EditEntry::New { name, .. } => name,
+        }
+    }
+}
+
+/// 在现有PAK基础上增删改条目的编辑器
+///
+/// 所有操作先记录下来，保存时才生成新的PAK。未修改的条目按原始字节复制，
+/// 保留原有的顺序和 FileInfo 元数据；替换的条目保留原来的位置和时间戳。
+pub struct PakEditor<R> {
+    reader: PakReader<R>,
+    entries: Vec<EditEntry>,
+    compress_level: u32,
+}
+
+impl PakEditor<BufReader<File>> {
+    /// 打开PAK文件进行编辑
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(PakReader::open(path)?))
+    }
+
+    /// 打开PAK文件进行编辑，按指定编码解析文件名
+    pub fn open_with_encoding(path: &Path, encoding: NameEncoding) -> io::Result<Self> {
+        Ok(Self::new(PakReader::open_with_encoding(path, encoding)?))
+    }
+
+    /// 保存到指定路径（可以是原文件，会先写入临时文件再替换）
+    pub fn save(mut self, path: &Path) -> io::Result<PakInfo> {
+        write_file_atomically(path, true, |writer| {
+            let pak_info = self.write_to(writer)?;
+            // 释放原文件后才能替换它
+            drop(self);
+            Ok(pak_info)
+        })
+    }
+}
+
+impl<R: Read + Seek> PakEditor<R> {
+    /// 基于已打开的PAK读取器创建编辑器
+    pub fn new(reader: PakReader<R>) -> Self {
+        let entries = reader.files().iter().enumerate()
+            .map(|(index, file_info)| EditEntry::Original {
+                index,
+                name: file_info.file_name.clone(),
+            })
+            .collect();
+
+        Self {
+            reader,
+            entries,
+            compress_level: DEFAULT_COMPRESS_LEVEL,
+        }
+    }
+
+    /// 设置新条目的压缩等级（仅对压缩模式的PAK有效）
+    pub fn compression_level(mut self, level: u32) -> Self {
+        self.compress_level = level;
+        self
+    }
+
+    /// 当前条目名列表（按写出顺序）
+    pub fn entry_names(&self) -> Vec<&str> {
+        self.entries.iter().map(|entry| entry.name()).collect()
+    }
+
+    /// 是否存在指定条目（不区分大小写，正反斜杠均可）
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// 添加新条目（内存数据），已存在同名条目时报错
+    pub fn add(&mut self, name: &str, data: Vec<u8>) -> io::Result<()> {
+        self.add_source(name, NewSource::Bytes(data))
+    }
+
+    /// 添加新条目（文件），已存在同名条目时报错
+    pub fn add_file(&mut self, name: &str, path: &Path) -> io::Result<()> {
+        self.add_source(name, NewSource::File(path.to_path_buf()))
+    }
+
+    /// 替换已有条目的内容（内存数据）
+    pub fn replace(&mut self, name: &str, data: Vec<u8>) -> io::Result<()> {
+        self.replace_source(name, NewSource::Bytes(data))
+    }
+
+    /// 替换已有条目的内容（文件）
+    pub fn replace_file(&mut self, name: &str, path: &Path) -> io::Result<()> {
+        self.replace_source(name, NewSource::File(path.to_path_buf()))
+    }
+
+    /// 删除条目
+    pub fn remove(&mut self, name: &str) -> io::Result<()> {
+        let position = self.require(name)?;
+        self.entries.remove(position);
+        Ok(())
+    }
+
+    /// 重命名条目（数据保持不变）
+    pub fn rename(&mut self, from: &str, to: &str) -> io::Result<()> {
+        let position = self.require(from)?;
+        let to = to.replace('/', "\\");
+        if let Some(existing) = self.position(&to) {
+            if existing != position {
+                return Err(PakError::DuplicateName { name: to }.into());
+            }
+        }
+
+        match &mut self.entries[position] {
+            EditEntry::Original { name, .. } | EditEntry::New { name, .. } => *name = to,
+        }
+        Ok(())
+    }
+
+    /// 将编辑结果写入任意输出，返回写入的PAK信息
+    ///
+    /// 加密、压缩模式和文件名编码与原PAK保持一致，未改名的条目保留原始文件名字节。
+    pub fn write_to<W: Write>(&mut self, writer: W) -> io::Result<PakInfo> {
+        let compressed = self.reader.is_compressed();
+        let key = self.reader.variant().xor_key.unwrap_or(0);
+        let mut writer = CryptWriter::new(writer, key);
+
+        // 先准备新条目的数据，得到头部需要的大小
+        let mut new_data = Vec::new();
+        let mut pak_info = PakInfo::new();
+        pak_info.version = self.reader.info().version;
+        pak_info.compress = Some(compressed);
+        pak_info.encoding = self.reader.encoding();
+
+        for entry in &self.entries {
+            let file_info = match entry {
+                EditEntry::Original { index, name } => {
+                    let mut file_info = self.reader.files()[*index].clone();
+                    file_info.file_name = name.clone();
+                    file_info
+                }
+                EditEntry::New { name, source, file_time } => {
+                    let data = match source {
+                        NewSource::Bytes(data) => data.clone(),
+                        NewSource::File(path) => fs::read(path)?,
+                    };
+                    let size = checked_size(data.len() as u64, name)?;
+                    let data = if compressed {
+                        compress_data(&data, self.compress_level)?
+                    } else {
+                        data
+                    };
+                    let file_info = FileInfo {
+                        file_name: name.clone(),
+                        z_size: checked_size(data.len() as u64, name)?,
+                        _size: size,
+                        _file_time: *file_time,
+                        raw_name: None,
+                    };
+                    new_data.push(data);
+                    file_info
+                }
+            };
+            pak_info.file_info_library.push(file_info);
+        }
+
+        write_pak_info(&mut writer, &pak_info)?;
+
+        // 写入数据：原条目直接复制原始字节，新条目写入准备好的数据
+        let mut new_data = new_data.into_iter();
+        for entry in &self.entries {
+            match entry {
+                EditEntry::Original { index, .. } => {
+                    let raw = self.reader.read_raw_entry(*index)?;
+                    writer.write_all(&raw)?;
+                }
+                EditEntry::New { .. } => {
+                    if let Some(data) = new_data.next() {
+                        writer.write_all(&data)?;
+                    }
+                }
+            }
+        }
+
+        writer.flush()?;
+        Ok(pak_info)
+    }
+
+    fn add_source(&mut self, name: &str, source: NewSource) -> io::Result<()> {
+        let name = name.replace('/', "\\");
+        if self.contains(&name) {
+            return Err(PakError::DuplicateName { name }.into());
+        }
+        if self.entries.len() >= PakInfo::MAX_ENTRIES {
+            return Err(PakError::TooManyEntries { limit: PakInfo::MAX_ENTRIES }.into());
+        }
+        self.entries.push(EditEntry::New {
+            name,
+            source,
+            file_time: PakInfo::DEFAULT_FILE_TIME,
+        });
+        Ok(())
+    }
+
+    fn replace_source(&mut self, name: &str, source: NewSource) -> io::Result<()> {
+        let position = self.require(name)?;
+        let (name, file_time) = match &self.entries[position] {
+            EditEntry::Original { index, name } => (name.clone(), self.reader.files()[*index]._file_time),
+            EditEntry::New { name, file_time, .. } => (name.clone(), *file_time),
+        };
+        self.entries[position] = EditEntry::New { name, source, file_time };
+        Ok(())
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        let key = normalize_entry_name(name);
+        self.entries.iter().position(|entry| normalize_entry_name(entry.name()) == key)
+    }
+
+    fn require(&self, name: &str) -> io::Result<usize> {
+        self.position(name).ok_or_else(|| {
+            PakError::EntryNotFound { name: name.to_string() }.into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::writer::PakWriter;
+
+    #[test]
+    fn editing_keeps_a_non_default_xor_key() {
+        let mut bytes = Vec::new();
+        let mut writer = PakWriter::new().xor_key(Some(0x5A));
+        writer.add_bytes("a.txt", b"hello".to_vec()).unwrap();
+        writer.write_to(&mut bytes).unwrap();
+
+        let mut editor = PakEditor::new(PakReader::new(Cursor::new(bytes)).unwrap());
+        editor.add("b.txt", b"world".to_vec()).unwrap();
+        let mut edited = Vec::new();
+        editor.write_to(&mut edited).unwrap();
+
+        let mut reader = PakReader::new(Cursor::new(edited)).unwrap();
+        assert_eq!(reader.variant().xor_key, Some(0x5A));
+        assert_eq!(reader.read_entry(0).unwrap(), b"hello");
+        assert_eq!(reader.read_entry(1).unwrap(), b"world");
+    }
+}
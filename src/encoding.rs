@@ -0,0 +1,131 @@
+use std::fmt;
+use std::str::FromStr;
+use encoding_rs::{Encoding, GBK, SHIFT_JIS, UTF_8, WINDOWS_1252};
+use crate::error::{PakError, PakResult};
+
+/// PAK索引中文件名使用的编码
+///
+/// 中文版使用GBK，日文版使用Shift-JIS，欧美版使用CP1252。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameEncoding {
+    /// GBK（与PopStudio一致，默认）
+    #[default]
+    Gbk,
+    /// Shift-JIS
+    ShiftJis,
+    /// Windows-1252
+    Windows1252,
+    /// UTF-8
+    Utf8,
+    /// 读取时根据文件名字节自动识别，写入时选择第一个能表示所有文件名的编码
+    Auto,
+}
+
+impl NameEncoding {
+    /// 对应的 encoding_rs 编码（Auto 按GBK处理）
+    fn encoding(self) -> &'static Encoding {
+        match self {
+            NameEncoding::Gbk | NameEncoding::Auto => GBK,
+            NameEncoding::ShiftJis => SHIFT_JIS,
+            NameEncoding::Windows1252 => WINDOWS_1252,
+            NameEncoding::Utf8 => UTF_8,
+        }
+    }
+
+    /// 解码文件名（无法解码的字节替换为 U+FFFD，原始字节应另行保存）
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let (decoded, _) = self.encoding().decode_without_bom_handling(bytes);
+        decoded.into_owned()
+    }
+
+    /// 编码文件名，存在无法表示的字符时报错
+    pub fn encode(self, name: &str) -> PakResult<Vec<u8>> {
+        let (encoded, _, had_errors) = self.encoding().encode(name);
+        if had_errors {
+            return Err(PakError::UnmappableName { name: name.to_string(), encoding: self });
+        }
+        Ok(encoded.into_owned())
+    }
+
+    /// 原始文件名是否能按该编码无损解码（Auto 按GBK处理）
+    pub fn decodes_cleanly(self, bytes: &[u8]) -> bool {
+        self.encoding().decode_without_bom_handling_and_without_replacement(bytes).is_some()
+    }
+
+    /// 根据索引中的原始文件名识别编码（非 Auto 时原样返回）
+    pub fn detect<'a>(self, names: impl IntoIterator<Item = &'a [u8]> + Clone) -> NameEncoding {
+        if self != NameEncoding::Auto {
+            return self;
+        }
+
+        let decodes_cleanly = |encoding: NameEncoding| {
+            names.clone().into_iter().all(|name| encoding.decodes_cleanly(name))
+        };
+
+        if names.clone().into_iter().all(|name| name.is_ascii()) {
+            return NameEncoding::Gbk;
+        }
+        if decodes_cleanly(NameEncoding::Utf8) {
+            return NameEncoding::Utf8;
+        }
+
+        // Shift-JIS的假名在GBK下也常能解码，因此先看是否出现了全角假名
+        let shift_jis_ok = decodes_cleanly(NameEncoding::ShiftJis);
+        let has_kana = names.clone().into_iter().any(|name| {
+            NameEncoding::ShiftJis.decode(name).chars().any(|c| ('\u{3040}'..='\u{30FF}').contains(&c))
+        });
+        if shift_jis_ok && has_kana {
+            return NameEncoding::ShiftJis;
+        }
+        if decodes_cleanly(NameEncoding::Gbk) {
+            return NameEncoding::Gbk;
+        }
+        if shift_jis_ok {
+            return NameEncoding::ShiftJis;
+        }
+        // CP1252 可以解码任意字节
+        NameEncoding::Windows1252
+    }
+
+    /// 为待写入的文件名选择编码（非 Auto 时原样返回）
+    pub fn resolve_for_names<'a>(self, names: impl IntoIterator<Item = &'a str> + Clone) -> NameEncoding {
+        if self != NameEncoding::Auto {
+            return self;
+        }
+
+        [NameEncoding::Gbk, NameEncoding::ShiftJis, NameEncoding::Windows1252]
+            .into_iter()
+            .find(|encoding| names.clone().into_iter().all(|name| encoding.encode(name).is_ok()))
+            .unwrap_or(NameEncoding::Utf8)
+    }
+}
+
+impl fmt::Display for NameEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NameEncoding::Gbk => "gbk",
+            NameEncoding::ShiftJis => "shift_jis",
+            NameEncoding::Windows1252 => "windows-1252",
+            NameEncoding::Utf8 => "utf-8",
+            NameEncoding::Auto => "auto",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for NameEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gbk" | "cp936" => Ok(NameEncoding::Gbk),
+            "shift_jis" | "shift-jis" | "sjis" | "cp932" => Ok(NameEncoding::ShiftJis),
+            "windows-1252" | "cp1252" | "latin1" => Ok(NameEncoding::Windows1252),
+            "utf-8" | "utf8" => Ok(NameEncoding::Utf8),
+            "auto" => Ok(NameEncoding::Auto),
+            _ => Err(format!(
+                "不支持的编码: {}（可选 gbk、shift_jis、windows-1252、utf-8、auto）", s
+            )),
+        }
+    }
+}
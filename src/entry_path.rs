@@ -0,0 +1,217 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// 条目路径被改写的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewriteReason {
+    /// 以分隔符开头的绝对路径
+    RootPrefix,
+    /// 以盘符（如 `C:`）开头
+    DrivePrefix,
+    /// 包含 `..` 组件
+    ParentDir,
+    /// 包含空组件或 `.` 组件
+    EmptyComponent,
+    /// 组件中含有 `:`（Windows 上为盘符相对路径或备用数据流）
+    Colon,
+    /// 组件是 Windows 保留的设备名（如 `CON`、`NUL`、`COM1`）
+    ReservedName,
+}
+
+impl fmt::Display for RewriteReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            RewriteReason::RootPrefix => "去掉了开头的根路径",
+            RewriteReason::DrivePrefix => "去掉了盘符",
+            RewriteReason::ParentDir => "去掉了 ..",
+            RewriteReason::EmptyComponent => "去掉了空路径或 .",
+            RewriteReason::Colon => "把 : 替换为 _",
+            RewriteReason::ReservedName => "在设备名前加了 _",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// 清理后的条目输出路径
+#[derive(Debug, Clone)]
+pub struct SafePath {
+    /// 相对于输出目录的路径（只包含普通组件）
+    pub relative: PathBuf,
+    /// 改写原因，为空表示路径未被改写
+    pub reasons: Vec<RewriteReason>,
+}
+
+impl SafePath {
+    /// 路径是否被改写过
+    pub fn is_rewritten(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+
+    /// 改写原因的文字说明
+    pub fn reason_text(&self) -> String {
+        self.reasons.iter().map(|reason| reason.to_string()).collect::<Vec<_>>().join("，")
+    }
+
+    /// 拼接到输出目录下
+    pub fn under(&self, output_dir: &Path) -> PathBuf {
+        output_dir.join(&self.relative)
+    }
+}
+
+/// 条目在输出目录中的布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathLayout {
+    /// 保留完整的条目路径
+    #[default]
+    Full,
+    /// 去掉开头的 N 层目录（同 tar 的 --strip-components）
+    StripComponents(usize),
+    /// 只保留文件名
+    Flatten,
+}
+
+impl PathLayout {
+    /// 按布局调整清理后的相对路径，层级不足时返回 None（该条目应被跳过）
+    pub fn apply(&self, relative: &Path) -> Option<PathBuf> {
+        let adjusted: PathBuf = match self {
+            PathLayout::Full => relative.to_path_buf(),
+            PathLayout::StripComponents(count) => relative.components().skip(*count).collect(),
+            PathLayout::Flatten => PathBuf::from(relative.file_name()?),
+        };
+        if adjusted.as_os_str().is_empty() {
+            return None;
+        }
+        Some(adjusted)
+    }
+}
+
+/// 将PAK条目名转换为可以安全写入输出目录的相对路径
+///
+/// 按 `\` 和 `/` 拆分为组件，去掉根路径、盘符、`..`、`.` 和空组件，
+/// 保证结果不会离开输出目录。组件中其余的 `:` 替换为 `_`，Windows 设备名前加 `_`，
+/// 在任何平台上都得到相同的结果。清理后没有剩余组件时返回 None（该条目应被拒绝）。
+pub fn sanitize_entry_path(name: &str) -> Option<SafePath> {
+    let mut reasons = Vec::new();
+    let mut note = |reason: RewriteReason| {
+        if !reasons.contains(&reason) {
+            reasons.push(reason);
+        }
+    };
+
+    if name.starts_with(['\\', '/']) {
+        note(RewriteReason::RootPrefix);
+    }
+
+    let mut relative = PathBuf::new();
+    for (index, component) in name.trim_start_matches(['\\', '/']).split(['\\', '/']).enumerate() {
+        // 盘符只可能出现在第一个组件（`C:foo` 保留 `foo`）
+        let component = if index == 0 && is_drive_prefix(component) {
+            note(RewriteReason::DrivePrefix);
+            match &component[2..] {
+                "" => continue,
+                rest => rest,
+            }
+        } else {
+            component
+        };
+
+        match component {
+            "" | "." => note(RewriteReason::EmptyComponent),
+            ".." => note(RewriteReason::ParentDir),
+            normal => {
+                let mut normal = normal.to_string();
+                if normal.contains(':') {
+                    note(RewriteReason::Colon);
+                    normal = normal.replace(':', "_");
+                }
+                if is_reserved_name(&normal) {
+                    note(RewriteReason::ReservedName);
+                    normal.insert(0, '_');
+                }
+                relative.push(normal);
+            }
+        }
+    }
+
+    if relative.as_os_str().is_empty() {
+        return None;
+    }
+    Some(SafePath { relative, reasons })
+}
+
+/// 形如 `C:` 的盘符
+fn is_drive_prefix(component: &str) -> bool {
+    let bytes = component.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// Windows 保留的设备名（不区分大小写，带扩展名或结尾空格时同样保留，如 `nul.txt`）
+fn is_reserved_name(component: &str) -> bool {
+    let stem = component.split('.').next().unwrap_or(component).trim_end_matches(' ');
+    let upper = stem.to_ascii_uppercase();
+    match upper.as_str() {
+        "CON" | "PRN" | "AUX" | "NUL" => true,
+        _ => {
+            let bytes = upper.as_bytes();
+            bytes.len() == 4
+                && (upper.starts_with("COM") || upper.starts_with("LPT"))
+                && (b'1'..=b'9').contains(&bytes[3])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sanitize(name: &str) -> Option<(String, Vec<RewriteReason>)> {
+        sanitize_entry_path(name).map(|safe| {
+            let parts: Vec<_> = safe.relative.iter().map(|part| part.to_string_lossy().into_owned()).collect();
+            (parts.join("/"), safe.reasons)
+        })
+    }
+
+    #[test]
+    fn plain_paths_are_kept() {
+        assert_eq!(sanitize("images\\plant.png"), Some(("images/plant.png".to_string(), vec![])));
+        assert_eq!(sanitize("a/b/c.txt"), Some(("a/b/c.txt".to_string(), vec![])));
+    }
+
+    #[test]
+    fn root_drive_and_parent_components_are_removed() {
+        assert_eq!(sanitize("\\a\\b.txt"), Some(("a/b.txt".to_string(), vec![RewriteReason::RootPrefix])));
+        assert_eq!(sanitize("C:\\a.txt"), Some(("a.txt".to_string(), vec![RewriteReason::DrivePrefix])));
+        assert_eq!(sanitize("C:a.txt"), Some(("a.txt".to_string(), vec![RewriteReason::DrivePrefix])));
+        assert_eq!(sanitize("a\\..\\..\\b.txt"), Some(("a/b.txt".to_string(), vec![RewriteReason::ParentDir])));
+        assert_eq!(sanitize("a\\\\.\\b.txt"), Some(("a/b.txt".to_string(), vec![RewriteReason::EmptyComponent])));
+    }
+
+    #[test]
+    fn colons_after_the_first_component_are_replaced() {
+        assert_eq!(sanitize("a\\C:evil"), Some(("a/C_evil".to_string(), vec![RewriteReason::Colon])));
+        assert_eq!(sanitize("foo:stream"), Some(("foo_stream".to_string(), vec![RewriteReason::Colon])));
+        assert_eq!(
+            sanitize("C:x\\y:z"),
+            Some(("x/y_z".to_string(), vec![RewriteReason::DrivePrefix, RewriteReason::Colon]))
+        );
+    }
+
+    #[test]
+    fn reserved_device_names_are_prefixed() {
+        assert_eq!(sanitize("a\\CON"), Some(("a/_CON".to_string(), vec![RewriteReason::ReservedName])));
+        assert_eq!(sanitize("nul.txt"), Some(("_nul.txt".to_string(), vec![RewriteReason::ReservedName])));
+        assert_eq!(sanitize("com1\\x"), Some(("_com1/x".to_string(), vec![RewriteReason::ReservedName])));
+        assert_eq!(sanitize("LPT9 .log"), Some(("_LPT9 .log".to_string(), vec![RewriteReason::ReservedName])));
+        assert_eq!(sanitize("console.txt"), Some(("console.txt".to_string(), vec![])));
+        assert_eq!(sanitize("COM10"), Some(("COM10".to_string(), vec![])));
+        assert_eq!(sanitize("LPT0"), Some(("LPT0".to_string(), vec![])));
+    }
+
+    #[test]
+    fn paths_without_normal_components_are_rejected() {
+        assert_eq!(sanitize(""), None);
+        assert_eq!(sanitize("\\"), None);
+        assert_eq!(sanitize("C:"), None);
+        assert_eq!(sanitize("..\\.."), None);
+    }
+}
@@ -0,0 +1,224 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use crate::encoding::NameEncoding;
+use crate::pak::PakInfo;
+
+/// PAK操作的错误类型
+///
+/// 可以通过 `?` 转换为 `io::Error`；从 `io::Error` 中可用 [`PakError::from_io`] 取回具体错误。
+#[derive(Debug)]
+pub enum PakError {
+    /// 底层IO错误
+    Io(io::Error),
+    /// 文件开头的magic不正确
+    BadMagic { found: u32 },
+    /// 索引中的条目标志不是0x00或0x80
+    BadEntryFlag { offset: u64, flag: u8 },
+    /// 索引在读完之前就到达了文件末尾
+    TruncatedIndex { offset: u64 },
+    /// 无法识别的PAK布局（所有候选布局的索引均无效）
+    UnknownLayout,
+    /// 读到索引长度上限仍没有找到索引结束标志
+    IndexTooLarge { limit: u64 },
+    /// 条目数量超过索引能容纳的上限
+    TooManyEntries { limit: usize },
+    /// 条目数据超出PAK文件边界
+    EntryOutOfBounds { name: String, offset: u64, size: u32, file_len: u64 },
+    /// 条目不存在
+    EntryNotFound { name: String },
+    /// 压缩条目解压失败
+    CorruptEntry { name: String, reason: String },
+    /// 解压后大小与索引记录的原始大小不一致
+    SizeMismatch { name: String, expected: u64, actual: u64 },
+    /// 写入时数据来源的长度与预期不一致（文件在打包过程中被修改）
+    SourceChanged { name: String, expected: u64, actual: u64 },
+    /// 文件名编码后超过255字节
+    NameTooLong { name: String, len: usize },
+    /// 文件名包含所选编码无法表示的字符
+    UnmappableName { name: String, encoding: NameEncoding },
+    /// 文件名重复
+    DuplicateName { name: String },
+    /// 文件超过4GB，无法用32位大小表示
+    EntryTooLarge { name: String, size: u64 },
+    /// 压缩等级不在0-9之间
+    InvalidCompressLevel { level: u32 },
+    /// 解包清单格式错误
+    BadManifest { line: usize, reason: String },
+    /// 补丁文件格式错误或与生成结果不符
+    BadPatch { reason: String },
+    /// 基础PAK的校验和与补丁记录的不一致
+    PatchBaseMismatch { expected: String, actual: String },
+    /// 环境变量 SOURCE_DATE_EPOCH 不是有效的Unix时间戳
+    InvalidSourceDateEpoch { value: String },
+    /// 输入路径不存在
+    InputNotFound { path: PathBuf },
+    /// 输入路径不是目录
+    NotADirectory { path: PathBuf },
+    /// 输入目录中没有任何文件
+    EmptyInput { path: PathBuf },
+    /// 文件扩展名不是 .pak
+    NotPakFile { path: PathBuf },
+    /// 文件头不是PAK magic
+    NotPakData { path: PathBuf },
+    /// 输出文件已存在
+    OutputExists { path: PathBuf },
+    /// 输出目录不为空
+    OutputNotEmpty { path: PathBuf },
+    /// 跟随符号链接时回到了正在遍历的上层目录
+    SymlinkLoop { path: PathBuf },
+    /// 符号链接策略为 error 时遇到符号链接
+    SymlinkNotAllowed { path: PathBuf },
+    /// 符号链接指向的目标不存在
+    BrokenSymlink { path: PathBuf },
+    /// 套接字、FIFO、设备文件等无法打包的文件
+    UnsupportedFileType { path: PathBuf, kind: &'static str },
+    /// 输入文件或目录无法读取（如没有权限）
+    UnreadableInput { path: PathBuf, source: io::Error },
+}
+
+/// PAK操作的结果类型
+pub type PakResult<T> = Result<T, PakError>;
+
+impl PakError {
+    /// 从 `io::Error` 中取回 PakError（如果该错误是由 PakError 转换而来）
+    pub fn from_io(err: &io::Error) -> Option<&PakError> {
+        err.get_ref().and_then(|inner| inner.downcast_ref::<PakError>())
+    }
+
+    /// 错误所在的字节偏移（如果有）
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            PakError::BadEntryFlag { offset, .. }
+            | PakError::TruncatedIndex { offset }
+            | PakError::EntryOutOfBounds { offset, .. } => Some(*offset),
+            PakError::BadMagic { .. } => Some(0),
+            _ => None,
+        }
+    }
+
+    /// 转换为 io::Error 时使用的错误类别
+    fn kind(&self) -> io::ErrorKind {
+        match self {
+            PakError::Io(e) => e.kind(),
+            PakError::UnreadableInput { source, .. } => source.kind(),
+            PakError::TruncatedIndex { .. }
+            | PakError::EntryOutOfBounds { .. }
+            | PakError::SourceChanged { .. } => io::ErrorKind::UnexpectedEof,
+            PakError::EntryNotFound { .. }
+            | PakError::InputNotFound { .. }
+            | PakError::BrokenSymlink { .. } => io::ErrorKind::NotFound,
+            PakError::OutputExists { .. } | PakError::OutputNotEmpty { .. } => io::ErrorKind::AlreadyExists,
+            PakError::NameTooLong { .. }
+            | PakError::UnmappableName { .. }
+            | PakError::DuplicateName { .. }
+            | PakError::EntryTooLarge { .. }
+            | PakError::TooManyEntries { .. }
+            | PakError::InvalidCompressLevel { .. }
+            | PakError::InvalidSourceDateEpoch { .. }
+            | PakError::NotADirectory { .. }
+            | PakError::EmptyInput { .. }
+            | PakError::NotPakFile { .. }
+            | PakError::NotPakData { .. }
+            | PakError::SymlinkLoop { .. }
+            | PakError::SymlinkNotAllowed { .. }
+            | PakError::UnsupportedFileType { .. }
+            | PakError::PatchBaseMismatch { .. } => io::ErrorKind::InvalidInput,
+            PakError::BadMagic { .. }
+            | PakError::BadEntryFlag { .. }
+            | PakError::UnknownLayout
+            | PakError::IndexTooLarge { .. }
+            | PakError::BadManifest { .. }
+            | PakError::BadPatch { .. }
+            | PakError::CorruptEntry { .. }
+            | PakError::SizeMismatch { .. } => io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl fmt::Display for PakError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PakError::Io(e) => write!(f, "{}", e),
+            PakError::BadMagic { found } => write!(
+                f, "无效的PAK magic: 期望 0x{:08X}，实际 0x{:08X}", PakInfo::MAGIC, found
+            ),
+            PakError::BadEntryFlag { offset, flag } => write!(
+                f, "无效的文件标志: 0x{:02X}，位于偏移 {}（应为 0x00 或 0x80）", flag, offset
+            ),
+            PakError::TruncatedIndex { offset } => write!(f, "索引在偏移 {} 处被截断", offset),
+            PakError::UnknownLayout => write!(f, "无法识别PAK格式: 所有候选布局的索引均无效"),
+            PakError::IndexTooLarge { limit } => write!(f, "索引超过 {} 字节仍没有结束标志", limit),
+            PakError::TooManyEntries { limit } => write!(f, "条目数量超过上限 {}", limit),
+            PakError::EntryOutOfBounds { name, offset, size, file_len } => write!(
+                f, "文件 {} 数据超出PAK文件边界（偏移 {}，大小 {}，文件长度 {}）", name, offset, size, file_len
+            ),
+            PakError::EntryNotFound { name } => write!(f, "PAK中不存在文件: {}", name),
+            PakError::CorruptEntry { name, reason } => write!(f, "文件 {} 解压失败: {}", name, reason),
+            PakError::SizeMismatch { name, expected, actual } => write!(
+                f, "文件 {} 解压后大小不匹配: 期望 {} 字节，实际 {} 字节", name, expected, actual
+            ),
+            PakError::SourceChanged { name, expected, actual } => write!(
+                f, "文件 {} 数据长度变化: 期望 {} 字节，实际 {} 字节", name, expected, actual
+            ),
+            PakError::NameTooLong { name, len } => write!(
+                f, "文件名过长: {}（{} 字节，最多 255）", name, len
+            ),
+            PakError::UnmappableName { name, encoding } => write!(
+                f, "文件名包含 {} 编码无法表示的字符: {}", encoding, name
+            ),
+            PakError::DuplicateName { name } => write!(f, "发现重复的文件名: {}", name),
+            PakError::EntryTooLarge { name, size } => write!(f, "文件过大 (>4GB): {}（{} 字节）", name, size),
+            PakError::InvalidCompressLevel { level } => write!(f, "压缩等级必须在 0-9 之间: {}", level),
+            PakError::BadManifest { line, reason } => write!(f, "清单文件第 {} 行无效: {}", line, reason),
+            PakError::BadPatch { reason } => write!(f, "无效的补丁文件: {}", reason),
+            PakError::PatchBaseMismatch { expected, actual } => write!(
+                f, "基础PAK与补丁不匹配: 补丁要求 sha256 {}，实际为 {}", expected, actual
+            ),
+            PakError::InvalidSourceDateEpoch { value } => write!(f, "无效的 SOURCE_DATE_EPOCH: {}", value),
+            PakError::InputNotFound { path } => write!(f, "输入路径不存在: {}", path.display()),
+            PakError::NotADirectory { path } => write!(f, "输入路径必须是目录: {}", path.display()),
+            PakError::EmptyInput { path } => write!(f, "输入目录为空: {}", path.display()),
+            PakError::NotPakFile { path } => write!(f, "文件必须是 .pak 文件: {}", path.display()),
+            PakError::NotPakData { path } => write!(f, "不是PAK文件（文件头不是PAK magic）: {}", path.display()),
+            PakError::OutputExists { path } => write!(f, "输出文件已存在: {}", path.display()),
+            PakError::OutputNotEmpty { path } => write!(f, "输出目录不为空: {}", path.display()),
+            PakError::SymlinkLoop { path } => write!(f, "符号链接形成循环: {}", path.display()),
+            PakError::SymlinkNotAllowed { path } => write!(f, "输入中包含符号链接: {}", path.display()),
+            PakError::BrokenSymlink { path } => write!(f, "符号链接的目标不存在: {}", path.display()),
+            PakError::UnsupportedFileType { path, kind } => write!(
+                f, "不支持的文件类型（{}）: {}", kind, path.display()
+            ),
+            PakError::UnreadableInput { path, source } => write!(f, "无法读取 {}: {}", path.display(), source),
+        }
+    }
+}
+
+impl Error for PakError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PakError::Io(e) | PakError::UnreadableInput { source: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PakError {
+    fn from(err: io::Error) -> Self {
+        // 由 PakError 转换而来的 io::Error 直接还原
+        match err.downcast::<PakError>() {
+            Ok(pak_err) => pak_err,
+            Err(err) => PakError::Io(err),
+        }
+    }
+}
+
+impl From<PakError> for io::Error {
+    fn from(err: PakError) -> Self {
+        match err {
+            PakError::Io(e) => e,
+            other => io::Error::new(other.kind(), other),
+        }
+    }
+}
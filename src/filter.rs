@@ -0,0 +1,239 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use regex::Regex;
+use crate::pak::FileInfo;
+use crate::reader::normalize_entry_name;
+
+/// 按条目名筛选要处理的条目
+///
+/// include、regex 和 from_list 选中的条目取并集（都为空时选中全部），再去掉 exclude 匹配的条目。
+/// 通配符和正则表达式匹配的是以 `/` 分隔的完整路径。
+#[derive(Debug, Clone, Default)]
+pub struct EntryFilter {
+    /// 包含的通配符模式
+    pub include: Vec<String>,
+    /// 排除的通配符模式
+    pub exclude: Vec<String>,
+    /// 包含的正则表达式
+    pub regex: Vec<String>,
+    /// 条目列表文件（每行一个条目名，不区分大小写）
+    pub from_list: Option<PathBuf>,
+}
+
+/// 筛选结果
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    /// 选中的条目索引（按PAK中的顺序）
+    pub indices: Vec<usize>,
+    /// 列表文件中在PAK里找不到的条目名
+    pub missing: Vec<String>,
+}
+
+impl EntryFilter {
+    /// 是否没有任何筛选条件
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.regex.is_empty() && self.from_list.is_none()
+    }
+
+    /// 筛选条目
+    pub fn select(&self, files: &[FileInfo]) -> io::Result<Selection> {
+        let mut missing = Vec::new();
+        let mut selected: BTreeSet<usize> = if self.include.is_empty() && self.regex.is_empty() && self.from_list.is_none() {
+            (0..files.len()).collect()
+        } else {
+            let mut selected = BTreeSet::new();
+            for pattern in &self.include {
+                selected.extend(filter_files_by_pattern(files, pattern));
+            }
+            for regex_pattern in &self.regex {
+                selected.extend(filter_files_by_regex(files, regex_pattern)?);
+            }
+            if let Some(list_path) = &self.from_list {
+                let (listed, not_found) = filter_files_by_list(files, list_path)?;
+                selected.extend(listed);
+                missing = not_found;
+            }
+            selected
+        };
+
+        for pattern in &self.exclude {
+            for index in filter_files_by_pattern(files, pattern) {
+                selected.remove(&index);
+            }
+        }
+
+        Ok(Selection { indices: selected.into_iter().collect(), missing })
+    }
+}
+
+/// 按列表文件筛选文件（空行和 # 开头的行被忽略），返回条目索引和找不到的条目名
+fn filter_files_by_list(files: &[FileInfo], list_path: &Path) -> io::Result<(Vec<usize>, Vec<String>)> {
+    let text = fs::read_to_string(list_path)?;
+    let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, file) in files.iter().enumerate() {
+        by_name.entry(normalize_entry_name(&file.file_name)).or_default().push(index);
+    }
+    
+    let mut result = Vec::new();
+    let mut missing = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        match by_name.get(&normalize_entry_name(line)) {
+            Some(indices) => result.extend(indices),
+            None => missing.push(line.to_string()),
+        }
+    }
+    
+    Ok((result, missing))
+}
+
+/// 根据文件名筛选文件，返回条目索引
+pub(crate) fn filter_files_by_name(files: &[FileInfo], filename: &str) -> Vec<usize> {
+    let mut result = Vec::new();
+    
+    for (index, file) in files.iter().enumerate() {
+        let unix_path = file.file_name.replace('\\', "/");
+        let file_basename = unix_path.split('/').next_back().unwrap_or(&unix_path);
+        if file_basename == filename {
+            result.push(index);
+        }
+    }
+    
+    result
+}
+
+/// 根据通配符模式筛选文件，返回条目索引
+pub(crate) fn filter_files_by_pattern(files: &[FileInfo], pattern: &str) -> Vec<usize> {
+    let mut result = Vec::new();
+    
+    for (index, file) in files.iter().enumerate() {
+        let unix_path = file.file_name.replace('\\', "/");
+        if matches_glob_pattern(&unix_path, pattern) {
+            result.push(index);
+        }
+    }
+    
+    result
+}
+
+/// 根据正则表达式筛选文件，返回条目索引
+pub(crate) fn filter_files_by_regex(files: &[FileInfo], regex_pattern: &str) -> io::Result<Vec<usize>> {
+    let regex = Regex::new(regex_pattern).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("正则表达式错误: {}", e))
+    })?;
+    
+    let mut result = Vec::new();
+    
+    for (index, file) in files.iter().enumerate() {
+        let unix_path = file.file_name.replace('\\', "/");
+        if regex.is_match(&unix_path) {
+            result.push(index);
+        }
+    }
+    
+    Ok(result)
+}
+
+/// 检查路径是否匹配通配符模式
+pub(crate) fn matches_glob_pattern(path: &str, pattern: &str) -> bool {
+    // 直接匹配，不进行路径分隔符转换，因为现在pattern已经是反斜杠格式
+    glob_match(path, pattern)
+}
+
+/// 实现基本的glob匹配
+fn glob_match(text: &str, pattern: &str) -> bool {
+    let text_chars: Vec<char> = text.chars().collect();
+    let pattern_chars: Vec<char> = pattern.chars().collect();
+    
+    glob_match_recursive(&text_chars, &pattern_chars, 0, 0)
+}
+
+fn glob_match_recursive(text: &[char], pattern: &[char], t_idx: usize, p_idx: usize) -> bool {
+    // 模式结束
+    if p_idx >= pattern.len() {
+        return t_idx >= text.len();
+    }
+    
+    // 文本结束但模式未结束
+    if t_idx >= text.len() {
+        // 检查剩余模式是否都是*
+        return pattern[p_idx..].iter().all(|&c| c == '*');
+    }
+    
+    match pattern[p_idx] {
+        '*' => {
+            // *匹配0个或多个字符
+            // 尝试匹配0个字符
+            if glob_match_recursive(text, pattern, t_idx, p_idx + 1) {
+                return true;
+            }
+            // 尝试匹配1个或多个字符
+            for i in t_idx..text.len() {
+                if glob_match_recursive(text, pattern, i + 1, p_idx + 1) {
+                    return true;
+                }
+            }
+            false
+        }
+        '?' => {
+            // ?匹配单个字符
+            glob_match_recursive(text, pattern, t_idx + 1, p_idx + 1)
+        }
+        '[' => {
+            // 字符类匹配
+            if let Some(end_bracket) = pattern[p_idx..].iter().position(|&c| c == ']') {
+                let char_class = &pattern[p_idx + 1..p_idx + end_bracket];
+                let current_char = text[t_idx];
+                
+                if matches_char_class(current_char, char_class) {
+                    glob_match_recursive(text, pattern, t_idx + 1, p_idx + end_bracket + 1)
+                } else {
+                    false
+                }
+            } else {
+                // 没有找到闭合的]，按字面量匹配
+                text[t_idx] == pattern[p_idx] && 
+                glob_match_recursive(text, pattern, t_idx + 1, p_idx + 1)
+            }
+        }
+        c => {
+            // 字面量字符匹配
+            text[t_idx] == c && glob_match_recursive(text, pattern, t_idx + 1, p_idx + 1)
+        }
+    }
+}
+
+fn matches_char_class(ch: char, char_class: &[char]) -> bool {
+    if char_class.is_empty() {
+        return false;
+    }
+    
+    let negated = char_class[0] == '!';
+    let chars_to_check = if negated { &char_class[1..] } else { char_class };
+    
+    let mut i = 0;
+    let mut matched = false;
+    
+    while i < chars_to_check.len() {
+        if i + 2 < chars_to_check.len() && chars_to_check[i + 1] == '-' {
+            // 范围匹配如 a-z
+            let start = chars_to_check[i];
+            let end = chars_to_check[i + 2];
+            if ch >= start && ch <= end {
+                matched = true;
+                break;
+            }
+            i += 3;
+        } else {
+            // 单个字符匹配
+            if ch == chars_to_check[i] {
+                matched = true;
+                break;
+            }
+            i += 1;
+        }
+    }
+    
+    if negated { !matched } else { matched }
+}
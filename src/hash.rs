@@ -0,0 +1,262 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek};
+use std::path::Path;
+use std::str::FromStr;
+use colored::*;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use crate::encoding::NameEncoding;
+use crate::entry_path::sanitize_entry_path;
+use crate::error::PakError;
+use crate::reader::PakReader;
+
+/// 校验和算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+    Crc32,
+    Md5,
+    Sha1,
+    #[default]
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// 所有算法
+    pub const ALL: [HashAlgorithm; 4] = [HashAlgorithm::Crc32, HashAlgorithm::Md5, HashAlgorithm::Sha1, HashAlgorithm::Sha256];
+
+    /// 十六进制校验和的长度
+    pub fn hex_len(self) -> usize {
+        match self {
+            HashAlgorithm::Crc32 => 8,
+            HashAlgorithm::Md5 => 32,
+            HashAlgorithm::Sha1 => 40,
+            HashAlgorithm::Sha256 => 64,
+        }
+    }
+
+    /// 按十六进制校验和的长度识别算法
+    pub fn from_hex_len(len: usize) -> Option<HashAlgorithm> {
+        HashAlgorithm::ALL.into_iter().find(|algorithm| algorithm.hex_len() == len)
+    }
+
+    /// 计算数据的校验和（小写十六进制）
+    pub fn hash_bytes(self, data: &[u8]) -> String {
+        let mut hasher = Hasher::new(self);
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    /// 边读取边计算校验和（小写十六进制）
+    pub fn hash_reader<R: Read>(self, mut reader: R) -> io::Result<String> {
+        let mut hasher = Hasher::new(self);
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+        }
+        Ok(hasher.finish())
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HashAlgorithm::Crc32 => "crc32",
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "").as_str() {
+            "crc32" => Ok(HashAlgorithm::Crc32),
+            "md5" => Ok(HashAlgorithm::Md5),
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            _ => Err(format!("不支持的校验算法: {}（可选 crc32、md5、sha1、sha256）", s)),
+        }
+    }
+}
+
+/// 各算法的增量计算状态
+enum Hasher {
+    Crc32(crc32fast::Hasher),
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Hasher {
+        match algorithm {
+            HashAlgorithm::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+            HashAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+            HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Crc32(hasher) => hasher.update(data),
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish(self) -> String {
+        match self {
+            Hasher::Crc32(hasher) => format!("{:08x}", hasher.finalize()),
+            Hasher::Md5(hasher) => to_hex(&hasher.finalize()),
+            Hasher::Sha1(hasher) => to_hex(&hasher.finalize()),
+            Hasher::Sha256(hasher) => to_hex(&hasher.finalize()),
+        }
+    }
+}
+
+/// 计算PAK中一个条目内容的校验和（压缩模式下为解压后的内容）
+pub fn hash_entry<R: Read + Seek>(reader: &mut PakReader<R>, index: usize, algorithm: HashAlgorithm) -> io::Result<String> {
+    algorithm.hash_reader(reader.open_entry_by_index(index)?)
+}
+
+/// 按 sha256sum 的格式输出PAK中每个条目的校验和（路径用 `/` 分隔）
+pub fn run_hash(pak_path: &Path, algorithm: HashAlgorithm, encoding: NameEncoding) -> io::Result<()> {
+    let mut reader = PakReader::open_with_encoding(pak_path, encoding)?;
+    for index in 0..reader.files().len() {
+        let hash = hash_entry(&mut reader, index, algorithm)?;
+        println!("{}  {}", hash, reader.files()[index].file_name.replace('\\', "/"));
+    }
+    Ok(())
+}
+
+/// 清单中的一行
+struct ChecksumLine {
+    algorithm: HashAlgorithm,
+    hash: String,
+    path: String,
+}
+
+/// 按 sha256sum 格式的清单检查PAK或解包目录，返回是否全部通过
+///
+/// 未指定算法时按每行校验和的长度识别。PAK中的条目名不区分大小写，`/` 和 `\` 等价。
+pub fn run_hash_check(target: &Path, manifest_path: &Path, algorithm: Option<HashAlgorithm>, encoding: NameEncoding) -> io::Result<bool> {
+    let text = fs::read_to_string(manifest_path)?;
+    let mut lines = Vec::new();
+    let mut malformed = 0;
+    for line in text.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_checksum_line(line, algorithm) {
+            Some(parsed) => lines.push(parsed),
+            None => malformed += 1,
+        }
+    }
+    if lines.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} 中没有格式正确的校验和行", manifest_path.display()),
+        ));
+    }
+
+    let mut source = if target.is_dir() {
+        CheckSource::Dir(target)
+    } else if target.exists() {
+        CheckSource::Pak(PakReader::open_with_encoding(target, encoding)?)
+    } else {
+        return Err(PakError::InputNotFound { path: target.to_path_buf() }.into());
+    };
+
+    let mut failed = 0;
+    let mut missing = 0;
+    for line in &lines {
+        match source.hash(&line.path, line.algorithm) {
+            Ok(Some(hash)) if hash == line.hash => println!("{}: {}", line.path, "OK".green()),
+            Ok(Some(_)) => {
+                println!("{}: {}", line.path, "FAILED".red());
+                failed += 1;
+            }
+            Ok(None) => {
+                println!("{}: {}", line.path, "不存在".red());
+                missing += 1;
+            }
+            Err(e) => {
+                println!("{}: {}（{}）", line.path, "无法读取".red(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    if malformed > 0 {
+        println!("{}", format!("警告: {} 行格式不正确", malformed).yellow());
+    }
+    if missing > 0 {
+        println!("{}", format!("警告: {} 个文件不存在", missing).yellow());
+    }
+    if failed > 0 {
+        println!("{}", format!("警告: {} 个文件校验失败", failed).yellow());
+    }
+    Ok(failed == 0 && missing == 0)
+}
+
+/// 被检查的对象
+enum CheckSource<'a> {
+    Pak(PakReader<BufReader<File>>),
+    Dir(&'a Path),
+}
+
+impl CheckSource<'_> {
+    /// 计算路径对应内容的校验和，不存在时返回 None
+    fn hash(&mut self, path: &str, algorithm: HashAlgorithm) -> io::Result<Option<String>> {
+        match self {
+            CheckSource::Pak(reader) => match reader.find_entry(path) {
+                Some(index) => hash_entry(reader, index, algorithm).map(Some),
+                None => Ok(None),
+            },
+            CheckSource::Dir(dir) => {
+                let Some(safe_path) = sanitize_entry_path(path) else {
+                    return Ok(None);
+                };
+                match File::open(dir.join(safe_path.relative)) {
+                    Ok(file) => algorithm.hash_reader(BufReader::new(file)).map(Some),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+        }
+    }
+}
+
+/// 解析 `校验和  路径` 或 `校验和 *路径` 格式的一行
+fn parse_checksum_line(line: &str, algorithm: Option<HashAlgorithm>) -> Option<ChecksumLine> {
+    let (hash, rest) = line.split_once(' ')?;
+    let path = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*'))?;
+    if path.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let detected = HashAlgorithm::from_hex_len(hash.len())?;
+    if algorithm.is_some_and(|algorithm| algorithm != detected) {
+        return None;
+    }
+    Some(ChecksumLine {
+        algorithm: detected,
+        hash: hash.to_ascii_lowercase(),
+        path: path.to_string(),
+    })
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
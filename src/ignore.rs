@@ -0,0 +1,320 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use regex::Regex;
+
+/// 打包时读取的忽略规则文件名（可以放在输入目录的任意子目录中）
+pub const IGNORE_FILE_NAME: &str = ".pakignore";
+
+/// 一条 gitignore 语法的规则
+///
+/// 支持 `#` 注释、`!` 取反、结尾 `/` 只匹配目录、`*`、`?`、`[abc]` 和 `**`。
+/// 不含 `/` 的模式匹配任意层级的文件名，含 `/` 的模式相对于规则文件所在目录。
+/// 与 PAK 条目名一致，匹配不区分大小写。
+#[derive(Debug, Clone)]
+pub struct IgnoreRule {
+    /// 规则来源（如 `images/.pakignore:3` 或 `--exclude`）
+    pub source: String,
+    /// 原始模式文本
+    pub pattern: String,
+    /// `!` 开头的规则重新包含之前被忽略的路径
+    pub negated: bool,
+    /// 只匹配目录
+    dir_only: bool,
+    /// 只匹配最后一级名称（模式中没有 `/`）
+    basename_only: bool,
+    /// 规则所在目录（相对输入目录，用 `/` 分隔，根目录为空）
+    base: String,
+    regex: Regex,
+}
+
+impl IgnoreRule {
+    /// 解析一行规则，空行和注释返回 None
+    pub fn parse(line: &str, base: &str, source: String) -> io::Result<Option<IgnoreRule>> {
+        let pattern = line.trim_end();
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return Ok(None);
+        }
+
+        let (negated, body) = match pattern.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, pattern.strip_prefix('\\').filter(|rest| rest.starts_with(['#', '!'])).unwrap_or(pattern)),
+        };
+        let (dir_only, body) = match body.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, body),
+        };
+        let basename_only = !body.contains('/');
+        let body = body.trim_start_matches('/');
+        if body.is_empty() {
+            return Ok(None);
+        }
+
+        let regex = Regex::new(&glob_to_regex(body)).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("忽略规则错误（{}）: {}: {}", source, pattern, e))
+        })?;
+        Ok(Some(IgnoreRule {
+            source,
+            pattern: pattern.to_string(),
+            negated,
+            dir_only,
+            basename_only,
+            base: base.to_string(),
+            regex,
+        }))
+    }
+
+    /// 读取规则文件中的所有规则
+    pub fn read_file(path: &Path, base: &str, source_name: &str) -> io::Result<Vec<IgnoreRule>> {
+        let text = fs::read_to_string(path)?;
+        let mut rules = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let source = format!("{}:{}", source_name, line_number + 1);
+            if let Some(rule) = IgnoreRule::parse(line, base, source)? {
+                rules.push(rule);
+            }
+        }
+        Ok(rules)
+    }
+
+    /// 规则是否匹配给定路径（相对输入目录，用 `/` 分隔）
+    pub fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let relative = if self.base.is_empty() {
+            path
+        } else {
+            match path.strip_prefix(self.base.as_str()).and_then(|rest| rest.strip_prefix('/')) {
+                Some(rest) => rest,
+                None => return false,
+            }
+        };
+        if self.basename_only {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            self.regex.is_match(name)
+        } else {
+            self.regex.is_match(relative)
+        }
+    }
+}
+
+impl fmt::Display for IgnoreRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}（{}）", self.pattern, self.source)
+    }
+}
+
+/// 按 gitignore 的规则找出决定路径是否被忽略的规则：最后一条匹配的规则生效，
+/// 它是 `!` 规则时路径不被忽略
+pub fn ignored_by<'a>(rules: impl IntoIterator<Item = &'a IgnoreRule>, path: &str, is_dir: bool) -> Option<&'a IgnoreRule> {
+    rules.into_iter()
+        .filter(|rule| rule.matches(path, is_dir))
+        .last()
+        .filter(|rule| !rule.negated)
+}
+
+/// 跳过的原因及数量
+#[derive(Debug, Clone)]
+pub struct SkipCount {
+    pub reason: String,
+    pub files: usize,
+    pub dirs: usize,
+}
+
+/// 打包时跳过的文件和目录统计（按原因首次出现的顺序）
+#[derive(Debug, Clone, Default)]
+pub struct SkipReport {
+    pub reasons: Vec<SkipCount>,
+}
+
+impl SkipReport {
+    /// 记录一个被跳过的文件或目录
+    pub fn record(&mut self, reason: String, is_dir: bool) {
+        let count = self.count_for(reason);
+        if is_dir {
+            count.dirs += 1;
+        } else {
+            count.files += 1;
+        }
+    }
+
+    /// 合并另一份统计
+    pub fn merge(&mut self, other: SkipReport) {
+        for other_count in other.reasons {
+            let count = self.count_for(other_count.reason);
+            count.files += other_count.files;
+            count.dirs += other_count.dirs;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reasons.is_empty()
+    }
+
+    /// 跳过的文件数量（被跳过的目录中的文件不计入）
+    pub fn file_count(&self) -> usize {
+        self.reasons.iter().map(|count| count.files).sum()
+    }
+
+    /// 跳过的目录数量
+    pub fn dir_count(&self) -> usize {
+        self.reasons.iter().map(|count| count.dirs).sum()
+    }
+
+    fn count_for(&mut self, reason: String) -> &mut SkipCount {
+        let index = match self.reasons.iter().position(|count| count.reason == reason) {
+            Some(index) => index,
+            None => {
+                self.reasons.push(SkipCount { reason, files: 0, dirs: 0 });
+                self.reasons.len() - 1
+            }
+        };
+        &mut self.reasons[index]
+    }
+}
+
+/// 把 gitignore 的通配符模式转换为正则表达式（`*` 和 `?` 不匹配 `/`）
+fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::from("(?i)^");
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                // `**/` 匹配零层或多层目录，其余位置的 `**` 匹配任意字符
+                if chars.get(i + 2) == Some(&'/') {
+                    regex.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    regex.push_str(".*");
+                    i += 2;
+                }
+            }
+            '*' => {
+                regex.push_str("[^/]*");
+                i += 1;
+            }
+            '?' => {
+                regex.push_str("[^/]");
+                i += 1;
+            }
+            '[' => match chars[i + 1..].iter().skip(1).position(|&c| c == ']') {
+                Some(offset) => {
+                    let end = i + 2 + offset;
+                    let mut class: String = chars[i + 1..end].iter().collect();
+                    if let Some(rest) = class.strip_prefix('!') {
+                        class = format!("^{}", rest);
+                    }
+                    regex.push('[');
+                    regex.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                    regex.push(']');
+                    i = end + 1;
+                }
+                None => {
+                    regex.push_str("\\[");
+                    i += 1;
+                }
+            },
+            '\\' if i + 1 < chars.len() => {
+                regex.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 2;
+            }
+            c => {
+                regex.push_str(&regex::escape(&c.to_string()));
+                i += 1;
+            }
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(line: &str, base: &str) -> IgnoreRule {
+        IgnoreRule::parse(line, base, "test".to_string()).unwrap().unwrap()
+    }
+
+    #[test]
+    fn glob_wildcards_do_not_cross_directories() {
+        let regex = Regex::new(&glob_to_regex("a*/b?.txt")).unwrap();
+        assert!(regex.is_match("abc/b1.txt"));
+        assert!(regex.is_match("A/B1.TXT"));
+        assert!(!regex.is_match("a/x/b1.txt"));
+        assert!(!regex.is_match("a/b12.txt"));
+    }
+
+    #[test]
+    fn double_star_matches_any_number_of_directories() {
+        let regex = Regex::new(&glob_to_regex("**/cache/**")).unwrap();
+        assert!(regex.is_match("cache/x"));
+        assert!(regex.is_match("a/b/cache/c/d"));
+        assert!(!regex.is_match("a/cached/x"));
+    }
+
+    #[test]
+    fn character_classes_and_escapes() {
+        let regex = Regex::new(&glob_to_regex("file[!0-9].[ch]")).unwrap();
+        assert!(regex.is_match("fileA.c"));
+        assert!(!regex.is_match("file1.c"));
+        assert!(!regex.is_match("fileA.o"));
+
+        let literal = Regex::new(&glob_to_regex("\\*.txt")).unwrap();
+        assert!(literal.is_match("*.txt"));
+        assert!(!literal.is_match("a.txt"));
+
+        let unclosed = Regex::new(&glob_to_regex("a[b")).unwrap();
+        assert!(unclosed.is_match("a[b"));
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        assert!(IgnoreRule::parse("# comment", "", "test".to_string()).unwrap().is_none());
+        assert!(IgnoreRule::parse("   ", "", "test".to_string()).unwrap().is_none());
+        let escaped = rule("\\#name", "");
+        assert!(escaped.matches("#name", false));
+        assert!(!escaped.negated);
+    }
+
+    #[test]
+    fn basename_patterns_match_at_any_depth_and_anchored_patterns_do_not() {
+        let basename = rule("*.bak", "");
+        assert!(basename.matches("a/b/c.bak", false));
+
+        let anchored = rule("/build", "");
+        assert!(anchored.matches("build", true));
+        assert!(!anchored.matches("src/build", true));
+
+        let nested = rule("docs/*.md", "");
+        assert!(nested.matches("docs/a.md", false));
+        assert!(!nested.matches("x/docs/a.md", false));
+    }
+
+    #[test]
+    fn dir_only_rules_skip_files() {
+        let dir_rule = rule("tmp/", "");
+        assert!(dir_rule.matches("a/tmp", true));
+        assert!(!dir_rule.matches("a/tmp", false));
+    }
+
+    #[test]
+    fn rules_are_relative_to_their_directory() {
+        let nested = rule("*.png", "images");
+        assert!(nested.matches("images/a/b.png", false));
+        assert!(!nested.matches("b.png", false));
+        assert!(!nested.matches("images2/b.png", false));
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let rules = [rule("*.log", ""), rule("!keep.log", ""), rule("logs/", "")];
+        assert!(ignored_by(&rules, "a.log", false).is_some());
+        assert!(ignored_by(&rules, "keep.log", false).is_none());
+        assert_eq!(ignored_by(&rules, "logs", true).unwrap().pattern, "logs/");
+        assert!(ignored_by(&rules, "a.txt", false).is_none());
+    }
+}
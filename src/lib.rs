@@ -1,13 +1,49 @@
 pub mod cli;
+pub mod encoding;
+pub mod entry_path;
+pub mod filter;
+pub mod ignore;
+pub mod overwrite;
+pub mod error;
 pub mod pak;
+pub mod probe;
+pub mod scan;
+pub mod reader;
+pub mod writer;
+pub mod editor;
+pub mod verify;
+pub mod diff;
+pub mod hash;
+pub mod patch;
+pub mod manifest;
 pub mod pack;
 pub mod unpack;
 pub mod repl;
+pub mod salvage;
 pub mod utils;
+pub mod walk;
 
 // 重新导出主要的公共类型和函数
+pub use encoding::NameEncoding;
+pub use entry_path::{sanitize_entry_path, PathLayout, SafePath};
+pub use filter::{EntryFilter, Selection};
+pub use ignore::{IgnoreRule, SkipReport, IGNORE_FILE_NAME};
+pub use overwrite::{write_file_with_policy, OverwritePolicy, WriteOutcome, WriteStats};
+pub use error::{PakError, PakResult};
 pub use pak::{FileInfo, PakInfo};
-pub use pack::pack_to_pak;
-pub use unpack::unpack_pak;
-pub use repl::{run_repl, run_batch_commands};
-pub use utils::{ensure_directory_exists, is_directory_empty}; 
\ No newline at end of file
+pub use probe::{probe, is_pak_file, PakVariant};
+pub use reader::{PakReader, EntryReader};
+pub use writer::{EntryOptions, PakWriter};
+pub use editor::PakEditor;
+pub use scan::{scan_index, scan_pak, IndexProblem, IndexRecord, IndexScan, PakScan};
+pub use verify::{format_verify_report, run_verify, verify_pak, verify_stream, VerifyCheck, VerifyIssue, VerifyReport};
+pub use hash::{hash_entry, run_hash, run_hash_check, HashAlgorithm};
+pub use diff::{diff_paks, run_diff, ChangeKind, DiffFormat, DiffOptions, EntryChange, EntryState};
+pub use patch::{apply_patch, create_patch, PakPatch, PatchEntry, PatchOptions, PatchSource};
+pub use manifest::{Manifest, ManifestEntry, MANIFEST_FILE_NAME};
+pub use pack::{collect_pack_files, pack_sources_to_pak, pack_to_pak, pack_to_pak_with_options, PackOptions, PackSource, SortOrder};
+pub use unpack::{unpack_pak, unpack_pak_with_options, UnpackOptions};
+pub use salvage::{salvage_pak, SalvageEntry, SalvageOptions, SalvageReport, SalvageStatus, SALVAGE_REPORT_FILE_NAME};
+pub use repl::{run_repl, run_repl_with_encoding, run_batch_commands, run_batch_commands_with_encoding, list_pak_directory, show_pak_info};
+pub use utils::{ensure_directory_exists, is_directory_empty};
+pub use walk::SymlinkPolicy; 
\ No newline at end of file
@@ -1,8 +1,12 @@
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+use std::io::{self, Write};
+use std::path::Path;
 
 // 导入库模块
-use pvz_pak_tool::cli::Cli;
-use pvz_pak_tool::{pack_to_pak, unpack_pak, run_repl, run_batch_commands};
+use pvz_pak_tool::cli::{Cli, Command, LayoutArgs, OverwriteArgs, PackArgs, PatchCommand};
+use pvz_pak_tool::probe::is_pak_file;
+use pvz_pak_tool::{apply_patch, create_patch, pack_sources_to_pak, unpack_pak_with_options, run_repl_with_encoding, run_batch_commands_with_encoding, list_pak_directory, show_pak_info, run_diff, run_hash, run_hash_check, run_verify, salvage_pak, DiffOptions, NameEncoding, PackOptions, PakReader, PatchOptions, SalvageOptions, UnpackOptions, MANIFEST_FILE_NAME};
 
 #[cfg(windows)]
 use colored::control;
@@ -16,53 +20,179 @@ fn main() {
             control::set_override(false);
         }
     }
-    
-    let cli = Cli::parse();
-    
-    let result = if let Some(output) = &cli.output {
+
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    let result = match cli.command {
+        Some(command) => run_command(command),
+        None => run_positional(cli, &matches),
+    };
+
+    // Ok(false) 表示校验或比较的结果不通过
+    match result {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 执行子命令
+fn run_command(command: Command) -> io::Result<bool> {
+    match command {
+        Command::Unpack { input, output, encoding, manifest, filter, overwrite, layout } => {
+            let options = UnpackOptions {
+                encoding: encoding.encoding,
+                manifest: manifest.map(|path| path.unwrap_or_else(|| output.join(MANIFEST_FILE_NAME))),
+                filter: filter.to_filter(),
+                overwrite: overwrite.to_policy(),
+                layout: layout.to_layout(),
+            };
+            unpack_pak_with_options(&input, &output, &options)?;
+        }
+        Command::Pack { input, output, pack, encoding, manifest, force } => {
+            let options = PackOptions { force, ..pack.to_options(encoding.encoding, manifest) };
+            pack_sources_to_pak(&pack.sources(input), &output, &options)?;
+        }
+        Command::Ls { pak, path, long, encoding } => {
+            list_pak_directory(&pak, path.as_deref(), long, encoding.encoding)?;
+        }
+        Command::Cat { pak, entry, encoding } => cat_entry(&pak, &entry, encoding.encoding)?,
+        Command::Info { pak, encoding } => show_pak_info(&pak, encoding.encoding)?,
+        Command::Verify { pak, encoding } => return run_verify(&pak, encoding.encoding),
+        Command::Hash { target, algo, check, encoding } => match check {
+            Some(manifest) => return run_hash_check(&target, &manifest, algo, encoding.encoding),
+            None => run_hash(&target, algo.unwrap_or_default(), encoding.encoding)?,
+        },
+        Command::Salvage { input, output, report, encoding, overwrite } => {
+            let options = SalvageOptions {
+                encoding: encoding.encoding,
+                overwrite: overwrite.to_policy(),
+                report,
+            };
+            salvage_pak(&input, &output, &options)?;
+        }
+        Command::Diff { old, new, format, content, encoding } => {
+            let options = DiffOptions { encoding: encoding.encoding, format, content };
+            return run_diff(&old, &new, &options);
+        }
+        Command::Patch { action } => match action {
+            PatchCommand::Create { base, modded, output, encoding, force } => {
+                let options = PatchOptions { encoding: encoding.encoding, force };
+                create_patch(&base, &modded, &output, &options)?;
+            }
+            PatchCommand::Apply { base, patch, output, encoding, force } => {
+                let options = PatchOptions { encoding: encoding.encoding, force };
+                apply_patch(&base, &patch, &output, &options)?;
+            }
+        },
+        Command::Shell { pak, commands, encoding } => {
+            if commands.is_empty() {
+                run_repl_with_encoding(&pak, encoding.encoding)?;
+            } else {
+                run_batch_commands_with_encoding(&pak, &commands, encoding.encoding)?;
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// 不带子命令的用法：目录打包，PAK文件（按文件头识别）解包、批处理或进入交互模式
+fn run_positional(cli: Cli, matches: &ArgMatches) -> io::Result<bool> {
+    let Some(input) = cli.input else {
+        Cli::command().print_help()?;
+        return Ok(false);
+    };
+    let encoding = cli.encoding.encoding;
+
+    // 每种用法只接受与之相关的参数，其余参数给出用法错误而不是忽略
+    let is_dir = input.is_dir();
+    let is_pak = !is_dir && is_pak_file(&input);
+    let (mode, unused_ids) = if is_dir {
+        ("打包目录", [OverwriteArgs::UNPACK_ONLY_ARG_IDS, LayoutArgs::ARG_IDS, &["commands"]].concat())
+    } else if is_pak && cli.output.is_some() {
+        ("解包PAK文件", [PackArgs::ARG_IDS, &["commands"]].concat())
+    } else if is_pak {
+        ("交互或批处理模式", [PackArgs::ARG_IDS, OverwriteArgs::ARG_IDS, LayoutArgs::ARG_IDS, &["manifest"]].concat())
+    } else {
+        ("", Vec::new())
+    };
+    let unused_args = Cli::given_args(matches, &unused_ids);
+    if !unused_args.is_empty() {
+        Cli::command().error(
+            ErrorKind::ArgumentConflict,
+            format!("{} 不能用于{}（输入: {}）", unused_args.join("、"), mode, input.display()),
+        ).exit();
+    }
+
+    if let Some(output) = &cli.output {
         // 有输出路径，执行打包或解包操作
-        if cli.input.is_dir() {
+        if is_dir {
             // 输入是目录，执行打包
-            pack_to_pak(&cli.input, output)
-        } else if cli.input.extension().map_or(false, |ext| ext == "pak") {
+            let options = PackOptions {
+                force: cli.overwrite.force,
+                ..cli.pack.to_options(encoding, cli.manifest.flatten())
+            };
+            pack_sources_to_pak(&cli.pack.sources(Some(input)), output, &options)?;
+        } else if is_pak {
             // 输入是PAK文件，执行解包
-            unpack_pak(&cli.input, output)
+            let options = UnpackOptions {
+                encoding,
+                manifest: cli.manifest.map(|path| path.unwrap_or_else(|| output.join(MANIFEST_FILE_NAME))),
+                overwrite: cli.overwrite.to_policy(),
+                layout: cli.layout.to_layout(),
+                ..UnpackOptions::default()
+            };
+            unpack_pak_with_options(&input, output, &options)?;
         } else {
             eprintln!("错误: 无法识别的输入类型");
             eprintln!("  - 打包: 输入应为目录");
-            eprintln!("  - 解包: 输入应为 .pak 文件");
+            eprintln!("  - 解包: 输入应为 PAK 文件");
             std::process::exit(1);
         }
     } else if !cli.commands.is_empty() {
         // 有命令参数，执行批处理模式
-        if cli.input.extension().map_or(false, |ext| ext == "pak") {
-            run_batch_commands(&cli.input, &cli.commands)
+        if is_pak {
+            run_batch_commands_with_encoding(&input, &cli.commands, encoding)?;
         } else {
-            eprintln!("错误: 批处理模式需要 .pak 文件作为输入");
+            eprintln!("错误: 批处理模式需要 PAK 文件作为输入");
             std::process::exit(1);
         }
     } else {
         // 没有输出路径也没有命令
-        if cli.input.is_dir() {
+        if is_dir {
             // 输入是目录但没有指定输出，要求指定输出PAK文件
             eprintln!("错误: 打包目录需要指定输出PAK文件");
             eprintln!("用法: pkt <目录> -o <输出.pak文件>");
             std::process::exit(1);
-        } else if cli.input.extension().map_or(false, |ext| ext == "pak") {
+        } else if is_pak {
             // 输入是PAK文件，进入REPL模式
-            run_repl(&cli.input)
+            run_repl_with_encoding(&input, encoding)?;
         } else {
             eprintln!("错误: 无法识别的输入类型");
             eprintln!("  - 打包: pkt <目录> -o <输出.pak文件>");
             eprintln!("  - 解包: pkt <输入.pak文件> -o <输出目录>");
             eprintln!("  - REPL: pkt <输入.pak文件>");
             eprintln!("  - 批处理: pkt <输入.pak文件> -c '命令1' -c '命令2'");
+            eprintln!("  - 子命令: pkt unpack|pack|ls|cat|info|verify|salvage|hash|diff|patch|shell，详见 pkt --help");
             std::process::exit(1);
         }
-    };
-    
-    if let Err(e) = result {
-        eprintln!("错误: {}", e);
-        std::process::exit(1);
+    }
+    Ok(true)
+}
+
+/// 将条目内容写到标准输出
+fn cat_entry(pak: &Path, entry: &str, encoding: NameEncoding) -> io::Result<()> {
+    let mut reader = PakReader::open_with_encoding(pak, encoding)?;
+    let mut entry_reader = reader.open_entry(entry)?;
+    let mut stdout = io::stdout().lock();
+
+    // 下游提前关闭管道（如 head）时正常退出
+    match io::copy(&mut entry_reader, &mut stdout).and_then(|_| stdout.flush()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
     }
 }
@@ -0,0 +1,305 @@
+use std::fs;
+use std::io::{self, Read, Seek};
+use std::path::Path;
+use crate::encoding::NameEncoding;
+use crate::error::PakError;
+use crate::reader::PakReader;
+use crate::utils::compress_data;
+
+/// 解包目录中清单文件的默认文件名（打包时不会作为条目写入）
+pub const MANIFEST_FILE_NAME: &str = ".pakmanifest";
+
+/// 清单文件的第一行
+const MANIFEST_HEADER: &str = "# pvz-pak-tool manifest v1";
+
+/// 解包清单：记录重新打包时需要还原的PAK元数据
+///
+/// 文本格式，每行一个以制表符分隔的记录（下例中制表符显示为空格）：
+///
+/// ```text
+/// # pvz-pak-tool manifest v1
+/// version  0
+/// key  0xF7
+/// compressed  false
+/// level  6
+/// encoding  gbk
+/// entry  129146222018596744  696d616765735c612e706e67  images\a.png
+/// ```
+///
+/// `level` 行只在压缩模式下、能识别出原压缩等级时存在。
+/// `entry` 行按PAK中的顺序排列，依次为 FILETIME、原始文件名字节（十六进制）和文件名（仅供阅读）。
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    /// 头部中的版本号
+    pub version: u32,
+    /// 异或密钥，None 表示未加密
+    pub xor_key: Option<u8>,
+    /// 是否为压缩模式
+    pub compressed: bool,
+    /// 识别出的zlib压缩等级（重新压缩得到相同字节的等级）
+    pub compress_level: Option<u32>,
+    /// 文件名编码
+    pub encoding: NameEncoding,
+    /// 条目（按PAK中的顺序）
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// 清单中的一个条目
+#[derive(Debug, Clone)]
+pub struct ManifestEntry {
+    /// 文件名（由原始字节按清单编码解码）
+    pub name: String,
+    /// 索引中的原始文件名字节
+    pub raw_name: Vec<u8>,
+    /// 文件时间戳（Windows FILETIME）
+    pub file_time: u64,
+}
+
+impl Manifest {
+    /// 从已打开的PAK生成清单
+    ///
+    /// 压缩模式下会读取第一个非空条目，识别原来使用的压缩等级。
+    pub fn from_reader<R: Read + Seek>(reader: &mut PakReader<R>) -> io::Result<Self> {
+        let encoding = reader.encoding();
+        let entries = reader.files().iter()
+            .map(|file_info| {
+                let raw_name = match &file_info.raw_name {
+                    Some(raw) => raw.clone(),
+                    None => encoding.encode(&file_info.file_name)?,
+                };
+                Ok(ManifestEntry {
+                    name: file_info.file_name.clone(),
+                    raw_name,
+                    file_time: file_info._file_time,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let compress_level = if reader.is_compressed() {
+            detect_compress_level(reader)?
+        } else {
+            None
+        };
+
+        Ok(Self {
+            version: reader.info().version,
+            xor_key: reader.variant().xor_key,
+            compressed: reader.is_compressed(),
+            compress_level,
+            encoding,
+            entries,
+        })
+    }
+
+    /// 写入清单文件
+    pub fn write_to_path(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::new();
+        text.push_str(MANIFEST_HEADER);
+        text.push('\n');
+        text.push_str(&format!("version\t{}\n", self.version));
+        match self.xor_key {
+            Some(key) => text.push_str(&format!("key\t0x{:02X}\n", key)),
+            None => text.push_str("key\tnone\n"),
+        }
+        text.push_str(&format!("compressed\t{}\n", self.compressed));
+        if let Some(level) = self.compress_level {
+            text.push_str(&format!("level\t{}\n", level));
+        }
+        text.push_str(&format!("encoding\t{}\n", self.encoding));
+
+        for entry in &self.entries {
+            // 文件名列只供阅读，去掉其中可能破坏格式的控制字符
+            let readable: String = entry.name.chars().filter(|c| !c.is_control()).collect();
+            text.push_str(&format!("entry\t{}\t{}\t{}\n", entry.file_time, to_hex(&entry.raw_name), readable));
+        }
+
+        fs::write(path, text)
+    }
+
+    /// 读取清单文件
+    pub fn read_from_path(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(Self::parse(&text)?)
+    }
+
+    /// 解析清单文本
+    pub fn parse(text: &str) -> Result<Self, PakError> {
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line));
+
+        match lines.next() {
+            Some((_, MANIFEST_HEADER)) => {}
+            _ => return Err(bad_line(1, "缺少清单文件头")),
+        }
+
+        let mut manifest = Manifest {
+            version: 0,
+            xor_key: None,
+            compressed: false,
+            compress_level: None,
+            encoding: NameEncoding::default(),
+            entries: Vec::new(),
+        };
+
+        for (line_no, line) in lines {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split('\t');
+            let kind = fields.next().unwrap_or_default();
+            let value = fields.next().ok_or_else(|| bad_line(line_no, "缺少字段"))?;
+
+            match kind {
+                "version" => {
+                    manifest.version = value.parse().map_err(|_| bad_line(line_no, "无效的版本号"))?;
+                }
+                "key" => {
+                    manifest.xor_key = match value {
+                        "none" => None,
+                        hex => {
+                            let digits = hex.strip_prefix("0x").unwrap_or(hex);
+                            let key = u8::from_str_radix(digits, 16).map_err(|_| bad_line(line_no, "无效的密钥"))?;
+                            if key == 0 { None } else { Some(key) }
+                        }
+                    };
+                }
+                "compressed" => {
+                    manifest.compressed = value.parse().map_err(|_| bad_line(line_no, "compressed 应为 true 或 false"))?;
+                }
+                "level" => {
+                    let level = value.parse().ok().filter(|level| *level <= 9);
+                    manifest.compress_level = Some(level.ok_or_else(|| bad_line(line_no, "压缩等级必须在 0-9 之间"))?);
+                }
+                "encoding" => {
+                    manifest.encoding = value.parse().map_err(|e: String| bad_line(line_no, &e))?;
+                }
+                "entry" => {
+                    let file_time = value.parse().map_err(|_| bad_line(line_no, "无效的时间戳"))?;
+                    let hex = fields.next().ok_or_else(|| bad_line(line_no, "缺少文件名字节"))?;
+                    let raw_name = from_hex(hex).ok_or_else(|| bad_line(line_no, "无效的文件名字节"))?;
+                    manifest.entries.push(ManifestEntry {
+                        name: String::new(),
+                        raw_name,
+                        file_time,
+                    });
+                }
+                other => return Err(bad_line(line_no, &format!("未知的记录类型: {}", other))),
+            }
+        }
+
+        // 编码可能写在条目之后，最后统一解码文件名
+        for entry in &mut manifest.entries {
+            entry.name = manifest.encoding.decode(&entry.raw_name);
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// 用各个压缩等级重新压缩第一个非空条目，找出与原始字节一致的等级
+fn detect_compress_level<R: Read + Seek>(reader: &mut PakReader<R>) -> io::Result<Option<u32>> {
+    let Some(index) = reader.files().iter().position(|file_info| file_info._size > 0) else {
+        return Ok(None);
+    };
+
+    let raw = reader.read_raw_entry(index)?;
+    let data = reader.read_entry(index)?;
+    for level in 0..=9 {
+        if compress_data(&data, level)? == raw {
+            return Ok(Some(level));
+        }
+    }
+    Ok(None)
+}
+
+fn bad_line(line: usize, reason: &str) -> PakError {
+    PakError::BadManifest { line, reason: reason.to_string() }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::writer::PakWriter;
+
+    #[test]
+    fn manifest_survives_a_write_and_read() {
+        let manifest = Manifest {
+            version: 3,
+            xor_key: Some(0x5A),
+            compressed: true,
+            compress_level: Some(9),
+            encoding: NameEncoding::ShiftJis,
+            entries: vec![
+                ManifestEntry { name: "a\\b.txt".to_string(), raw_name: b"a\\b.txt".to_vec(), file_time: 1 },
+                ManifestEntry { name: "\u{30c6}".to_string(), raw_name: vec![0x83, 0x65], file_time: u64::MAX },
+            ],
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(MANIFEST_FILE_NAME);
+        manifest.write_to_path(&path).unwrap();
+
+        let read = Manifest::read_from_path(&path).unwrap();
+        assert_eq!(read.version, 3);
+        assert_eq!(read.xor_key, Some(0x5A));
+        assert!(read.compressed);
+        assert_eq!(read.compress_level, Some(9));
+        assert_eq!(read.encoding, NameEncoding::ShiftJis);
+        assert_eq!(read.entries.len(), 2);
+        for (read, original) in read.entries.iter().zip(&manifest.entries) {
+            assert_eq!(read.name, original.name);
+            assert_eq!(read.raw_name, original.raw_name);
+            assert_eq!(read.file_time, original.file_time);
+        }
+    }
+
+    #[test]
+    fn manifest_from_a_compressed_pak_detects_the_level() {
+        let mut writer = PakWriter::new().compression(Some(3)).xor_key(None);
+        writer.add_bytes("a.txt", b"hello hello hello".to_vec()).unwrap();
+        let mut bytes = Vec::new();
+        writer.write_to(&mut bytes).unwrap();
+
+        let manifest = Manifest::from_reader(&mut PakReader::new(Cursor::new(bytes)).unwrap()).unwrap();
+        assert!(manifest.compressed);
+        assert_eq!(manifest.xor_key, None);
+        assert_eq!(manifest.entries[0].raw_name, b"a.txt");
+        // 小数据在相邻等级下可能压缩出相同的字节，识别出的等级只需能还原原始字节
+        let level = manifest.compress_level.unwrap();
+        assert_eq!(compress_data(b"hello hello hello", level).unwrap(), compress_data(b"hello hello hello", 3).unwrap());
+    }
+
+    #[test]
+    fn bad_lines_report_their_line_number() {
+        let header_only = format!("{}\n", MANIFEST_HEADER);
+        assert!(Manifest::parse(&header_only).unwrap().entries.is_empty());
+
+        let cases = [
+            ("version\t0\n", 1),
+            ("# pvz-pak-tool manifest v1\nversion\tx\n", 2),
+            ("# pvz-pak-tool manifest v1\nlevel\t10\n", 2),
+            ("# pvz-pak-tool manifest v1\n\nentry\t1\t6g\ta\n", 3),
+            ("# pvz-pak-tool manifest v1\nunknown\t1\n", 2),
+        ];
+        for (text, expected_line) in cases {
+            match Manifest::parse(text) {
+                Err(PakError::BadManifest { line, .. }) => assert_eq!(line, expected_line, "{:?}", text),
+                other => panic!("{:?}: {:?}", text, other.map(|_| ())),
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::error::PakError;
+use crate::utils::{system_time_to_filetime, write_file_with_time};
+
+/// 输出文件已存在时的处理方式（解包和 find -extract 共用）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    /// 不覆盖：解包要求输出目录为空
+    #[default]
+    Refuse,
+    /// 直接覆盖已存在的文件
+    Force,
+    /// 保留已存在的文件
+    SkipExisting,
+    /// 只在大小或修改时间不同时覆盖
+    Update,
+    /// 覆盖前把内容不同的已存在文件改名为 .bak
+    Backup,
+}
+
+impl fmt::Display for OverwritePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OverwritePolicy::Refuse => "refuse",
+            OverwritePolicy::Force => "force",
+            OverwritePolicy::SkipExisting => "skip-existing",
+            OverwritePolicy::Update => "update",
+            OverwritePolicy::Backup => "backup",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 按策略写入一个文件的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// 文件原本不存在
+    Created,
+    /// 覆盖了已存在的文件
+    Overwritten,
+    /// 已存在的文件被保留
+    Skipped,
+    /// 已存在的文件被改名为给出的备份路径后写入
+    BackedUp(PathBuf),
+}
+
+/// 写入结果的统计
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteStats {
+    pub created: usize,
+    pub overwritten: usize,
+    pub skipped: usize,
+    pub backed_up: usize,
+}
+
+impl WriteStats {
+    /// 记录一次写入结果
+    pub fn record(&mut self, outcome: &WriteOutcome) {
+        match outcome {
+            WriteOutcome::Created => self.created += 1,
+            WriteOutcome::Overwritten => self.overwritten += 1,
+            WriteOutcome::Skipped => self.skipped += 1,
+            WriteOutcome::BackedUp(_) => self.backed_up += 1,
+        }
+    }
+
+    /// 实际写入的文件数量
+    pub fn written(&self) -> usize {
+        self.created + self.overwritten + self.backed_up
+    }
+}
+
+/// 按策略把数据写入文件，并把修改时间设置为 FILETIME 对应的时间
+///
+/// `Refuse` 遇到已存在的文件时返回 `PakError::OutputExists`。
+/// `Update` 比较文件大小和修改时间（精确到秒，兼容时间精度较低的文件系统）。
+pub fn write_file_with_policy(path: &Path, data: &[u8], file_time: u64, policy: OverwritePolicy) -> io::Result<WriteOutcome> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            write_file_with_time(path, data, file_time)?;
+            return Ok(WriteOutcome::Created);
+        }
+        Err(e) => return Err(e),
+    };
+
+    match policy {
+        OverwritePolicy::Refuse => Err(PakError::OutputExists { path: path.to_path_buf() }.into()),
+        OverwritePolicy::SkipExisting => Ok(WriteOutcome::Skipped),
+        OverwritePolicy::Update => {
+            let same_size = metadata.len() == data.len() as u64;
+            let same_time = metadata.modified()
+                .map(|time| system_time_to_filetime(time) / 10_000_000 == file_time / 10_000_000)
+                .unwrap_or(false);
+            if same_size && same_time {
+                return Ok(WriteOutcome::Skipped);
+            }
+            write_file_with_time(path, data, file_time)?;
+            Ok(WriteOutcome::Overwritten)
+        }
+        OverwritePolicy::Force => {
+            write_file_with_time(path, data, file_time)?;
+            Ok(WriteOutcome::Overwritten)
+        }
+        OverwritePolicy::Backup => {
+            // 内容相同的文件没有需要保留的修改，不留备份
+            if metadata.len() == data.len() as u64 && fs::read(path)? == data {
+                write_file_with_time(path, data, file_time)?;
+                return Ok(WriteOutcome::Overwritten);
+            }
+            let backup_path = backup_path_for(path);
+            fs::rename(path, &backup_path)?;
+            write_file_with_time(path, data, file_time)?;
+            Ok(WriteOutcome::BackedUp(backup_path))
+        }
+    }
+}
+
+/// 选择一个不存在的备份路径：name.bak、name.bak.1、name.bak.2 ...
+fn backup_path_for(path: &Path) -> PathBuf {
+    let mut base = path.as_os_str().to_owned();
+    base.push(".bak");
+    let mut candidate = PathBuf::from(&base);
+    let mut counter = 1;
+    while fs::symlink_metadata(&candidate).is_ok() {
+        let mut numbered = base.clone();
+        numbered.push(format!(".{}", counter));
+        candidate = PathBuf::from(numbered);
+        counter += 1;
+    }
+    candidate
+}
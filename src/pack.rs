@@ -1,169 +1,416 @@
-use std::fs::{self, File};
-use std::io::{self, Write, BufWriter};
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
-use std::collections::HashSet;
-use crate::pak::{FileInfo, PakInfo};
-use crate::utils::{write_string_by_u8_head, crypt_data};
-
-/// 收集目录中的所有文件
-pub fn collect_files(dir: &Path, base_dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
-    let mut files = Vec::new();
-    let entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
-    
-    // 不排序，使用文件系统原始顺序
-    for entry in entries {
-        let path = entry.path();
-        let relative_path = path.strip_prefix(base_dir)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
-        let relative_str = relative_path.to_string_lossy().replace('/', "\\"); // 使用Windows风格路径
+use std::str::FromStr;
+use std::collections::HashMap;
+use std::fmt;
+use crate::encoding::NameEncoding;
+use crate::entry_path::sanitize_entry_path;
+use crate::error::PakError;
+use crate::ignore::{ignored_by, IgnoreRule, SkipReport, IGNORE_FILE_NAME};
+use crate::manifest::{Manifest, MANIFEST_FILE_NAME};
+use crate::salvage::SALVAGE_REPORT_FILE_NAME;
+use crate::reader::normalize_entry_name;
+use crate::utils::{format_filetime, source_date_epoch};
+use crate::walk::{classify_entry, read_dir_entries, DirId, SourceEntry, SymlinkPolicy};
+use crate::writer::{EntryOptions, PakWriter};
+
+/// 清单中没有识别出压缩等级时使用的默认压缩等级
+const MANIFEST_COMPRESS_LEVEL: u32 = 6;
+
+/// 打包时条目的排列顺序
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// 文件系统返回的原始顺序（不同文件系统上可能不同，需要显式指定）
+    Filesystem,
+    /// 按条目名逐字节排序（默认，在任何机器上得到相同的顺序）
+    #[default]
+    Name,
+    /// 不区分大小写排序（PopCap 资源包的顺序）
+    PopCap,
+    /// 按列表文件中的顺序，未列出的文件按条目名排序后追加
+    List(PathBuf),
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fs" | "none" => Ok(SortOrder::Filesystem),
+            "name" => Ok(SortOrder::Name),
+            "popcap" => Ok(SortOrder::PopCap),
+            _ => Err(format!("不支持的排序方式: {}（可选 name、popcap、fs）", s)),
+        }
+    }
+}
+
+/// 打包的一个来源：目录或单个文件，条目名加上 PAK 内的前缀
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackSource {
+    /// 来源目录或文件
+    pub path: PathBuf,
+    /// PAK 内的目录前缀（用 `\` 分隔，为空表示根目录）
+    pub prefix: String,
+}
+
+impl PackSource {
+    /// 放在 PAK 根目录下的来源
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), prefix: String::new() }
+    }
+    
+    /// 放在 PAK 内指定目录下的来源
+    pub fn with_prefix(path: impl Into<PathBuf>, prefix: &str) -> Self {
+        let prefix = prefix.replace('/', "\\").trim_matches('\\').to_string();
+        Self { path: path.into(), prefix }
+    }
+    
+    /// 来源中的相对路径对应的条目名
+    fn entry_name(&self, relative_path: &str) -> String {
+        if self.prefix.is_empty() {
+            relative_path.to_string()
+        } else {
+            format!("{}\\{}", self.prefix, relative_path)
+        }
+    }
+}
+
+impl FromStr for PackSource {
+    type Err = String;
+    
+    /// 解析 `SRC[=PAK_PREFIX]`（按最后一个 `=` 拆分）
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rsplit_once('=') {
+            Some(("", _)) => Err(format!("缺少来源路径: {}", s)),
+            Some((path, prefix)) => Ok(PackSource::with_prefix(path, prefix)),
+            None if s.is_empty() => Err("来源路径不能为空".to_string()),
+            None => Ok(PackSource::new(s)),
+        }
+    }
+}
+
+impl fmt::Display for PackSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.prefix.is_empty() {
+            write!(f, "{}", self.path.display())
+        } else {
+            write!(f, "{}={}", self.path.display(), self.prefix)
+        }
+    }
+}
+
+/// 打包选项
+#[derive(Debug, Clone, Default)]
+pub struct PackOptions {
+    /// zlib压缩等级（0-9），None 表示不压缩
+    pub compress_level: Option<u32>,
+    /// 所有条目使用的固定时间戳（Windows FILETIME）
+    ///
+    /// None 时如果设置了环境变量 SOURCE_DATE_EPOCH 则使用它，否则使用文件的修改时间。
+    pub file_time: Option<u64>,
+    /// 文件名编码（Auto 时选择第一个能表示所有文件名的编码）
+    pub encoding: NameEncoding,
+    /// 解包清单路径，None 时使用输入目录下的 `.pakmanifest`（如果存在）
+    pub manifest: Option<PathBuf>,
+    /// 忽略解包清单
+    pub ignore_manifest: bool,
+    /// 条目的排列顺序（使用清单时只影响清单之外的文件）
+    pub sort: SortOrder,
+    /// 只打包匹配这些模式的文件（gitignore 语法，为空时打包全部）
+    pub include: Vec<String>,
+    /// 额外的忽略规则（gitignore 语法，优先于 `.pakignore`）
+    pub exclude: Vec<String>,
+    /// 对符号链接的处理方式
+    pub symlinks: SymlinkPolicy,
+    /// 替换已存在的输出文件
+    pub force: bool,
+}
+
+/// 按忽略规则收集文件时的状态
+struct PackFileCollector {
+    /// `.pakignore` 中的规则（外层目录在前）
+    ignore_rules: Vec<IgnoreRule>,
+    /// 命令行的排除规则
+    exclude_rules: Vec<IgnoreRule>,
+    /// 命令行的包含规则
+    include_rules: Vec<IgnoreRule>,
+    /// 对符号链接的处理方式
+    symlinks: SymlinkPolicy,
+    /// 正在遍历的目录链（用于检测符号链接循环）
+    ancestors: Vec<DirId>,
+    files: Vec<(String, PathBuf)>,
+    skipped: SkipReport,
+}
+
+impl PackFileCollector {
+    /// 递归收集目录中的文件，被忽略的目录不会进入
+    fn collect(&mut self, dir: &Path, relative_dir: &str) -> io::Result<()> {
+        // 当前目录的 .pakignore 只作用于该目录及其子目录
+        let outer_rule_count = self.ignore_rules.len();
+        let ignore_file = dir.join(IGNORE_FILE_NAME);
+        if ignore_file.is_file() {
+            let source_name = join_relative(relative_dir, IGNORE_FILE_NAME);
+            self.ignore_rules.extend(IgnoreRule::read_file(&ignore_file, relative_dir, &source_name)?);
+        }
         
-        if path.is_file() {
-            files.push((relative_str, path));
-        } else if path.is_dir() {
-            // 递归处理子目录
-            let mut sub_files = collect_files(&path, base_dir)?;
-            files.append(&mut sub_files);
+        // 不排序，使用文件系统原始顺序
+        for entry in read_dir_entries(dir)? {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let relative_path = join_relative(relative_dir, &name);
+            
+            // 先按规则筛选，被忽略的特殊文件和失效链接不会报错
+            let is_dir = path.is_dir();
+            if !is_dir && name == IGNORE_FILE_NAME {
+                self.skipped.record(format!("{} 规则文件", IGNORE_FILE_NAME), false);
+                continue;
+            }
+            if let Some(rule) = ignored_by(self.ignore_rules.iter().chain(&self.exclude_rules), &relative_path, is_dir) {
+                self.skipped.record(rule.to_string(), is_dir);
+                continue;
+            }
+            
+            let dir_id = match classify_entry(&path, self.symlinks)? {
+                SourceEntry::File => None,
+                SourceEntry::Dir(dir_id) => Some(dir_id),
+                SourceEntry::SkippedSymlink => {
+                    self.skipped.record("符号链接".to_string(), is_dir);
+                    continue;
+                }
+            };
+            
+            if let Some(dir_id) = dir_id {
+                if self.ancestors.contains(&dir_id) {
+                    return Err(PakError::SymlinkLoop { path }.into());
+                }
+                self.ancestors.push(dir_id);
+                self.collect(&path, &relative_path)?;
+                self.ancestors.pop();
+            } else if self.is_included(&relative_path) {
+                self.files.push((relative_path.replace('/', "\\"), path)); // 使用Windows风格路径
+            } else {
+                self.skipped.record("不匹配 --include".to_string(), false);
+            }
         }
+        
+        self.ignore_rules.truncate(outer_rule_count);
+        Ok(())
     }
     
-    Ok(files)
+    /// 文件本身或它所在的某一级目录匹配包含规则
+    fn is_included(&self, relative_path: &str) -> bool {
+        if self.include_rules.is_empty() {
+            return true;
+        }
+        let mut parents = relative_path.match_indices('/').map(|(index, _)| &relative_path[..index]);
+        self.include_rules.iter().any(|rule| rule.matches(relative_path, false))
+            || parents.any(|parent| self.include_rules.iter().any(|rule| rule.matches(parent, true)))
+    }
+}
+
+/// 拼接用 `/` 分隔的相对路径
+fn join_relative(relative_dir: &str, name: &str) -> String {
+    if relative_dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", relative_dir, name)
+    }
+}
+
+/// 收集需要打包的文件，跳过 `.pakignore` 和包含/排除规则排除的文件
+///
+/// 符号链接按 `options.symlinks` 处理；符号链接循环、特殊文件和无法读取的文件会返回错误。
+pub fn collect_pack_files(input_dir: &Path, options: &PackOptions) -> io::Result<(Vec<(String, PathBuf)>, SkipReport)> {
+    let parse_rules = |patterns: &[String], source: &str| -> io::Result<Vec<IgnoreRule>> {
+        let mut rules = Vec::new();
+        for pattern in patterns {
+            rules.extend(IgnoreRule::parse(pattern, "", source.to_string())?);
+        }
+        Ok(rules)
+    };
+    let mut collector = PackFileCollector {
+        ignore_rules: Vec::new(),
+        exclude_rules: parse_rules(&options.exclude, "--exclude")?,
+        include_rules: parse_rules(&options.include, "--include")?,
+        symlinks: options.symlinks,
+        ancestors: vec![DirId::of(input_dir)?],
+        files: Vec::new(),
+        skipped: SkipReport::default(),
+    };
+    collector.collect(input_dir, "")?;
+    Ok((collector.files, collector.skipped))
 }
 
 /// 将目录打包为PAK文件
 pub fn pack_to_pak(input_dir: &Path, output_path: &Path) -> io::Result<()> {
-    // 验证输入目录
-    if !input_dir.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("输入目录不存在: {}", input_dir.display())
-        ));
+    pack_to_pak_with_options(input_dir, output_path, &PackOptions::default())
+}
+
+/// 将目录打包为PAK文件（带选项）
+pub fn pack_to_pak_with_options(input_dir: &Path, output_path: &Path, options: &PackOptions) -> io::Result<()> {
+    if input_dir.exists() && !input_dir.is_dir() {
+        return Err(PakError::NotADirectory { path: input_dir.to_path_buf() }.into());
+    }
+    pack_sources_to_pak(&[PackSource::new(input_dir)], output_path, options)
+}
+
+/// 将多个目录或文件合并打包为PAK文件
+///
+/// 来源按顺序合并，条目名相同（不区分大小写）时后面的来源覆盖前面的，
+/// 条目保留在第一次出现的位置。第一个来源是放在根目录下的目录时，自动使用其中的 `.pakmanifest`。
+pub fn pack_sources_to_pak(sources: &[PackSource], output_path: &Path, options: &PackOptions) -> io::Result<()> {
+    if let Some(level) = options.compress_level {
+        if level > 9 {
+            return Err(PakError::InvalidCompressLevel { level }.into());
+        }
     }
     
-    if !input_dir.is_dir() {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "输入路径必须是目录"
-        ));
+    // 验证输入
+    let Some(first_source) = sources.first() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "没有指定打包来源"));
+    };
+    for source in sources {
+        if !source.path.exists() {
+            return Err(PakError::InputNotFound { path: source.path.clone() }.into());
+        }
     }
     
     // 验证输出文件
-    if let Some(ext) = output_path.extension() {
-        if ext != "pak" {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "输出文件必须是 .pak 文件"
-            ));
-        }
-    } else {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "输出文件必须是 .pak 文件"
-        ));
+    if !output_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pak")) {
+        return Err(PakError::NotPakFile { path: output_path.to_path_buf() }.into());
     }
     
     // 检查输出文件是否已存在
-    if output_path.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::AlreadyExists,
-            format!("输出文件已存在: {}", output_path.display())
-        ));
+    if !options.force && output_path.exists() {
+        return Err(PakError::OutputExists { path: output_path.to_path_buf() }.into());
     }
     
-    println!("正在打包: {}", input_dir.display());
+    for source in sources {
+        println!("正在打包: {}", source);
+    }
     println!("输出文件: {}", output_path.display());
     
-    // 收集所有文件
-    let files = collect_files(input_dir, input_dir)?;
+    // 收集所有来源的文件，后面的来源覆盖前面的同名条目
+    // 同一来源中只有大小写或分隔符不同的文件不算覆盖，交给写入器的重名检查
+    let mut files: Vec<(String, PathBuf)> = Vec::new();
+    let mut winners: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut skipped = SkipReport::default();
+    let mut override_count = 0;
+    for (source_index, source) in sources.iter().enumerate() {
+        for (entry_name, file_path) in collect_source_files(source, options, &mut skipped)? {
+            match winners.get_mut(&normalize_entry_name(&entry_name)) {
+                Some((index, previous_source)) if *previous_source != source_index => {
+                    println!(
+                        "覆盖条目: {}，使用 {}（覆盖 {}）",
+                        entry_name, source, sources[*previous_source]
+                    );
+                    files[*index] = (entry_name, file_path);
+                    *previous_source = source_index;
+                    override_count += 1;
+                }
+                Some(_) => files.push((entry_name, file_path)),
+                None => {
+                    winners.insert(normalize_entry_name(&entry_name), (files.len(), source_index));
+                    files.push((entry_name, file_path));
+                }
+            }
+        }
+    }
+    if override_count > 0 {
+        println!("{} 个条目被后面的来源覆盖", override_count);
+    }
+    
+    if !skipped.is_empty() {
+        println!("跳过了 {} 个文件和 {} 个目录:", skipped.file_count(), skipped.dir_count());
+        for count in &skipped.reasons {
+            let mut parts = Vec::new();
+            if count.files > 0 {
+                parts.push(format!("{} 个文件", count.files));
+            }
+            if count.dirs > 0 {
+                parts.push(format!("{} 个目录", count.dirs));
+            }
+            println!("  {}: {}", count.reason, parts.join("，"));
+        }
+    }
     
     if files.is_empty() {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "输入目录为空"
-        ));
+        return Err(PakError::EmptyInput { path: first_source.path.clone() }.into());
     }
     
     println!("找到 {} 个文件", files.len());
+    sort_files(&mut files, &options.sort)?;
     
-    // 检查文件名重复
-    let mut file_names = HashSet::new();
-    for (relative_path, _) in &files {
-        if !file_names.insert(relative_path.clone()) {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("发现重复的文件名: {}", relative_path)
-            ));
+    // 固定时间戳：显式指定的优先，其次是 SOURCE_DATE_EPOCH
+    let fixed_time = match options.file_time {
+        Some(file_time) => Some(file_time),
+        None => {
+            let epoch = source_date_epoch()?;
+            if let Some(file_time) = epoch {
+                println!("使用 SOURCE_DATE_EPOCH 时间戳: {}", format_filetime(file_time));
+            }
+            epoch
         }
-    }
+    };
     
-    // 构建文件信息
-    let mut file_infos = Vec::new();
-    for (relative_path, file_path) in &files {
-        let metadata = fs::metadata(file_path)?;
-        let file_size = metadata.len();
-        
-        if file_size > u32::MAX as u64 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("文件过大 (>4GB): {}", relative_path)
-            ));
+    // 读取解包清单
+    let manifest_path = if options.ignore_manifest {
+        None
+    } else {
+        options.manifest.clone().or_else(|| {
+            let default_path = first_source.path.join(MANIFEST_FILE_NAME);
+            (first_source.prefix.is_empty() && default_path.is_file()).then_some(default_path)
+        })
+    };
+    let manifest = match &manifest_path {
+        Some(path) => {
+            println!("使用清单: {}", path.display());
+            Some(Manifest::read_from_path(path)?)
         }
-        
-        file_infos.push(FileInfo {
-            file_name: relative_path.clone(),
-            z_size: file_size as u32,
-            _size: file_size as u32,
-            _file_time: PakInfo::DEFAULT_FILE_TIME,
+        None => None,
+    };
+    
+    let mut writer = PakWriter::new()
+        .compression(options.compress_level)
+        .encoding(options.encoding);
+    if let Some(manifest) = &manifest {
+        // 清单记录的格式优先，显式指定的压缩等级除外
+        let compress_level = options.compress_level.or_else(|| {
+            manifest.compressed.then_some(manifest.compress_level.unwrap_or(MANIFEST_COMPRESS_LEVEL))
         });
+        writer = writer
+            .compression(compress_level)
+            .xor_key(manifest.xor_key)
+            .version(manifest.version)
+            .encoding(manifest.encoding);
     }
-    
-    // 创建PAK文件
-    let mut pak_info = PakInfo::new();
-    pak_info.file_info_library = file_infos;
-    pak_info.compress = Some(false); // 不压缩模式
-    
-    let output_file = File::create(output_path)?;
-    let mut writer = BufWriter::new(output_file);
-    
-    // 写入头部
-    writer.write_all(&PakInfo::MAGIC.to_le_bytes())?;
-    writer.write_all(&PakInfo::VERSION.to_le_bytes())?;
-    
-    // 写入文件信息
-    for file_info in &pak_info.file_info_library {
-        writer.write_all(&[0u8])?; // flag
-        write_string_by_u8_head(&mut writer, &file_info.file_name)?;
-        writer.write_all(&file_info.z_size.to_le_bytes())?;
-        
-        // 如果启用压缩，写入原始大小
-        if pak_info.compress.unwrap_or(false) {
-            writer.write_all(&file_info._size.to_le_bytes())?;
-        }
-        
-        // 总是写入文件时间戳
-        writer.write_all(&file_info._file_time.to_le_bytes())?;
+    if let Some(file_time) = fixed_time {
+        writer = writer.file_time(file_time);
     }
+    let mut writer = writer
+        .on_progress(|index, total| {
+            if index % 100 == 0 {
+                println!("正在打包: {}/{}", index + 1, total);
+            }
+        });
     
-    // 写入结束标志
-    writer.write_all(&[PakInfo::INFO_END])?;
-    
-    // 写入文件数据
-    for (index, (_, file_path)) in files.iter().enumerate() {
-        if index % 100 == 0 {
-            println!("正在打包: {}/{}", index + 1, files.len());
+    // 添加所有文件（写入器会检查文件名重复）
+    match &manifest {
+        Some(manifest) => add_files_by_manifest(&mut writer, files, manifest, options)?,
+        None => {
+            for (relative_path, file_path) in &files {
+                writer.add_file(relative_path, file_path)?;
+            }
         }
-        
-        let file_data = fs::read(file_path)?;
-        writer.write_all(&file_data)?;
     }
     
-    // 刷新缓冲区
-    writer.flush()?;
-    drop(writer);
-    
-    // 处理文件格式
-    let mut pak_data = fs::read(output_path)?;
-    crypt_data(&mut pak_data);
-    fs::write(output_path, pak_data)?;
+    // 先写入同一目录下的临时文件，完成后再改名，中途失败不会留下不完整的PAK
+    let pak_info = if options.force {
+        writer.write_to_path(output_path)?
+    } else {
+        writer.write_to_new_path(output_path)?
+    };
     
     println!("打包完成！生成了包含 {} 个文件的PAK", pak_info.file_info_library.len());
     
@@ -172,4 +419,110 @@ pub fn pack_to_pak(input_dir: &Path, output_path: &Path) -> io::Result<()> {
     println!("输出文件大小: {:.2} MB", output_size as f64 / 1024.0 / 1024.0);
     
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// 收集一个来源中的文件，返回条目名和文件路径（根目录下的清单文件和救援报告不作为条目）
+fn collect_source_files(source: &PackSource, options: &PackOptions, skipped: &mut SkipReport) -> io::Result<Vec<(String, PathBuf)>> {
+    if source.path.is_dir() {
+        let (mut files, source_skipped) = collect_pack_files(&source.path, options)?;
+        files.retain(|(relative_path, _)| relative_path != MANIFEST_FILE_NAME && relative_path != SALVAGE_REPORT_FILE_NAME);
+        skipped.merge(source_skipped);
+        return Ok(files.into_iter()
+            .map(|(relative_path, file_path)| (source.entry_name(&relative_path), file_path))
+            .collect());
+    }
+    
+    // 单个文件放在前缀目录下，保留文件名
+    match classify_entry(&source.path, options.symlinks)? {
+        SourceEntry::File => {
+            let file_name = source.path.file_name().unwrap_or_default().to_string_lossy();
+            Ok(vec![(source.entry_name(&file_name), source.path.clone())])
+        }
+        SourceEntry::Dir(_) => Ok(Vec::new()),
+        SourceEntry::SkippedSymlink => {
+            skipped.record("符号链接".to_string(), false);
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// 按清单的顺序和元数据添加文件，清单之外的文件追加在末尾
+fn add_files_by_manifest(
+    writer: &mut PakWriter,
+    files: Vec<(String, PathBuf)>,
+    manifest: &Manifest,
+    options: &PackOptions,
+) -> io::Result<()> {
+    let order: Vec<String> = files.iter().map(|(relative_path, _)| relative_path.clone()).collect();
+    let mut remaining: HashMap<String, PathBuf> = files.into_iter().collect();
+    let mut missing_count = 0;
+    
+    for entry in &manifest.entries {
+        // 与解包时相同的路径清理规则，找到条目在目录中的位置
+        let file_path = sanitize_entry_path(&entry.name)
+            .map(|safe_path| safe_path.relative.to_string_lossy().replace('/', "\\"))
+            .and_then(|relative_path| remaining.remove(&relative_path));
+        let Some(file_path) = file_path else {
+            println!("警告: 清单中的文件不存在，已跳过: {}", entry.name);
+            missing_count += 1;
+            continue;
+        };
+        
+        let entry_options = EntryOptions {
+            // 显式指定的固定时间戳优先于清单
+            file_time: options.file_time.is_none().then_some(entry.file_time),
+            raw_name: Some(entry.raw_name.clone()),
+        };
+        writer.add_file_with_options(&entry.name, &file_path, entry_options)?;
+    }
+    
+    // 清单之外的新文件按收集顺序追加
+    let mut extra_count = 0;
+    for relative_path in order {
+        if let Some(file_path) = remaining.remove(&relative_path) {
+            writer.add_file(&relative_path, &file_path)?;
+            extra_count += 1;
+        }
+    }
+    
+    if missing_count > 0 {
+        println!("清单中有 {} 个文件不存在", missing_count);
+    }
+    if extra_count > 0 {
+        println!("追加了 {} 个清单之外的文件", extra_count);
+    }
+    Ok(())
+}
+
+/// 按指定方式排列收集到的文件
+fn sort_files(files: &mut [(String, PathBuf)], order: &SortOrder) -> io::Result<()> {
+    match order {
+        SortOrder::Filesystem => {}
+        SortOrder::Name => files.sort_by(|a, b| a.0.cmp(&b.0)),
+        SortOrder::PopCap => files.sort_by_cached_key(|(name, _)| (name.to_lowercase(), name.clone())),
+        SortOrder::List(list_path) => {
+            // 列表中每行一个条目名，空行和 # 开头的行被忽略
+            let text = fs::read_to_string(list_path)?;
+            let mut positions = HashMap::new();
+            for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+                let next = positions.len();
+                positions.entry(normalize_entry_name(line)).or_insert(next);
+            }
+            
+            let listed_count = files.iter()
+                .filter(|(name, _)| positions.contains_key(&normalize_entry_name(name)))
+                .count();
+            files.sort_by_cached_key(|(name, _)| {
+                (positions.get(&normalize_entry_name(name)).copied().unwrap_or(usize::MAX), name.clone())
+            });
+            
+            if listed_count < positions.len() {
+                println!("警告: 排序列表中有 {} 个条目不存在", positions.len() - listed_count);
+            }
+            if listed_count < files.len() {
+                println!("警告: {} 个文件不在排序列表中，已按名称排在末尾", files.len() - listed_count);
+            }
+        }
+    }
+    Ok(())
+}
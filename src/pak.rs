@@ -1,13 +1,18 @@
-use std::io;
-use crate::utils::{read_string_by_u8_head, read_u32_le, read_u64_le};
+use std::borrow::Cow;
+use std::io::Write;
+use crate::encoding::NameEncoding;
+use crate::error::{PakError, PakResult};
+use crate::utils::{read_bytes_by_u8_head, write_bytes_by_u8_head, read_u32_le, read_u64_le, decompress_data};
 
 /// PAK文件中的文件信息
 #[derive(Debug, Clone)]
 pub struct FileInfo {
     pub file_name: String,
     pub z_size: u32,
-    pub _size: u32,      // 原始大小（压缩模式下使用，但当前实现不需要）
-    pub _file_time: u64, // 文件时间戳（从PAK读取但不使用）
+    pub _size: u32,      // 原始大小（仅压缩模式下存在）
+    pub _file_time: u64, // 文件时间戳（Windows FILETIME）
+    /// 索引中的原始文件名字节（名字未被修改时原样写回，保证往返不变）
+    pub raw_name: Option<Vec<u8>>,
 }
 
 /// PAK文件信息
@@ -19,6 +24,8 @@ pub struct PakInfo {
     pub compress: Option<bool>,
     pub pc: bool,
     pub win: bool,
+    /// 文件名编码（读取时为识别出的编码）
+    pub encoding: NameEncoding,
 }
 
 impl PakInfo {
@@ -26,6 +33,12 @@ impl PakInfo {
     pub const VERSION: u32 = 0x0;
     pub const INFO_END: u8 = 0x80;
     pub const DEFAULT_FILE_TIME: u64 = 129146222018596744;
+    /// 索引最多包含的条目数（远多于游戏资源包中的条目数，用于限制读取索引时的内存）
+    pub const MAX_ENTRIES: usize = 1 << 18;
+    /// 一条索引记录的最大长度：标志、文件名长度、255字节文件名、两个大小字段和时间戳
+    pub const MAX_RECORD_LEN: usize = 1 + 1 + 255 + 4 + 4 + 8;
+    /// 索引区域（包括 magic、版本和结束标志）的最大长度
+    pub const MAX_INDEX_SIZE: usize = 8 + Self::MAX_ENTRIES * Self::MAX_RECORD_LEN + 1;
     
     pub fn new() -> Self {
         Self {
@@ -35,32 +48,40 @@ impl PakInfo {
             compress: None,
             pc: true,
             win: true,
+            encoding: NameEncoding::default(),
         }
     }
 }
 
-/// 解析PAK文件头
-pub fn parse_pak_info(data: &[u8]) -> io::Result<(PakInfo, usize)> {
+impl Default for PakInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 解析PAK文件头（data 为解密后的数据，compressed 由 probe 探测得到）
+///
+/// encoding 为 Auto 时根据所有文件名的字节识别编码。
+pub fn parse_pak_info(data: &[u8], compressed: bool, encoding: NameEncoding) -> PakResult<(PakInfo, usize)> {
     let mut pos = 0;
     let mut pak_info = PakInfo::new();
+    pak_info.compress = Some(compressed);
     
     // 读取并验证magic
     let magic = read_u32_le(data, &mut pos)?;
     if magic != PakInfo::MAGIC {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("Invalid PAK magic: expected 0x{:08X}, got 0x{:08X}", PakInfo::MAGIC, magic)
-        ));
+        return Err(PakError::BadMagic { found: magic });
     }
     
     // 读取版本
     let version = read_u32_le(data, &mut pos)?;
     pak_info.version = version;
     
-    // 读取文件条目
+    // 读取文件条目（先保留原始文件名字节，确定编码后再解码）
+    let mut raw_names = Vec::new();
     loop {
         if pos >= data.len() {
-            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Unexpected end of data"));
+            return Err(PakError::TruncatedIndex { offset: pos as u64 });
         }
         
         let flag = data[pos];
@@ -69,49 +90,14 @@ pub fn parse_pak_info(data: &[u8]) -> io::Result<(PakInfo, usize)> {
         if flag == PakInfo::INFO_END {
             break;
         } else if flag != 0 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData, 
-                format!("Invalid file flag: 0x{:02X} at position {} (expected 0x00 or 0x80)", flag, pos - 1)
-            ));
-        }
-        
-        // 自动检测压缩模式（仅在第一个文件时）
-        if pak_info.compress.is_none() {
-            let saved_pos = pos;
-            
-            // 跳过文件名
-            if pos >= data.len() {
-                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Cannot read filename length"));
-            }
-            let name_len = data[pos] as usize;
-            pos += 1 + name_len;
-            
-            // 跳过z_size
-            pos += 4;
-            
-            // 检测是否有size字段（压缩模式）
-            if pos + 12 < data.len() {
-                pos += 4; // 假设有size字段
-                pos += 8; // 跳过timestamp
-                
-                // 检查下一个标志位
-                if pos < data.len() && (data[pos] == 0 || data[pos] == PakInfo::INFO_END) {
-                    pak_info.compress = Some(true);
-                } else {
-                    pak_info.compress = Some(false);
-                }
-            } else {
-                pak_info.compress = Some(false);
-            }
-            
-            pos = saved_pos; // 恢复位置
+            return Err(PakError::BadEntryFlag { offset: (pos - 1) as u64, flag });
         }
         
         // 读取文件信息
-        let file_name = read_string_by_u8_head(data, &mut pos)?;
+        let raw_name = read_bytes_by_u8_head(data, &mut pos)?;
         let z_size = read_u32_le(data, &mut pos)?;
         
-        let size = if pak_info.compress.unwrap_or(false) {
+        let size = if compressed {
             read_u32_le(data, &mut pos)?
         } else {
             0
@@ -119,19 +105,92 @@ pub fn parse_pak_info(data: &[u8]) -> io::Result<(PakInfo, usize)> {
         
         let file_time = read_u64_le(data, &mut pos)?;
         
+        raw_names.push(raw_name);
         pak_info.file_info_library.push(FileInfo {
-            file_name,
+            file_name: String::new(),
             z_size,
             _size: size,
             _file_time: file_time,
+            raw_name: Some(raw_name.to_vec()),
         });
     }
     
+    let encoding = encoding.detect(raw_names.iter().copied());
+    for (file_info, raw_name) in pak_info.file_info_library.iter_mut().zip(raw_names) {
+        file_info.file_name = encoding.decode(raw_name);
+    }
+    pak_info.encoding = encoding;
+    
     Ok((pak_info, pos))
 }
 
+/// 写入PAK文件头（magic、版本和文件索引）
+///
+/// 写入前先编码所有文件名，任何文件名无法编码时不会写出任何数据。
+pub fn write_pak_info<W: Write>(writer: &mut W, pak_info: &PakInfo) -> PakResult<()> {
+    let names = encode_names(pak_info)?;
+    
+    writer.write_all(&PakInfo::MAGIC.to_le_bytes())?;
+    writer.write_all(&pak_info.version.to_le_bytes())?;
+    
+    for (file_info, name) in pak_info.file_info_library.iter().zip(&names) {
+        writer.write_all(&[0u8])?; // flag
+        write_bytes_by_u8_head(writer, name, &file_info.file_name)?;
+        writer.write_all(&file_info.z_size.to_le_bytes())?;
+        
+        // 如果启用压缩，写入原始大小
+        if pak_info.compress.unwrap_or(false) {
+            writer.write_all(&file_info._size.to_le_bytes())?;
+        }
+        
+        // 总是写入文件时间戳
+        writer.write_all(&file_info._file_time.to_le_bytes())?;
+    }
+    
+    // 写入结束标志
+    writer.write_all(&[PakInfo::INFO_END])?;
+    Ok(())
+}
+
+/// 编码索引中的所有文件名
+///
+/// 原始字节解码后与当前文件名一致时原样使用，否则按 PakInfo 的编码重新编码。
+fn encode_names(pak_info: &PakInfo) -> PakResult<Vec<Vec<u8>>> {
+    let files = &pak_info.file_info_library;
+    let encoding = pak_info.encoding.resolve_for_names(files.iter().map(|f| f.file_name.as_str()));
+    
+    files.iter()
+        .map(|file_info| match &file_info.raw_name {
+            Some(raw) if encoding.decode(raw) == file_info.file_name => Ok(raw.clone()),
+            _ => encoding.encode(&file_info.file_name),
+        })
+        .collect()
+}
+
+/// 获取文件条目的实际内容（压缩模式下解压并校验原始大小）
+pub fn decode_entry_data<'a>(raw: &'a [u8], file_info: &FileInfo, compressed: bool) -> PakResult<Cow<'a, [u8]>> {
+    if compressed {
+        let data = decompress_data(raw, file_info._size).map_err(|e| PakError::CorruptEntry {
+            name: file_info.file_name.clone(),
+            reason: e.to_string(),
+        })?;
+        
+        if data.len() != file_info._size as usize {
+            return Err(PakError::SizeMismatch {
+                name: file_info.file_name.clone(),
+                expected: file_info._size as u64,
+                actual: data.len() as u64,
+            });
+        }
+        
+        Ok(Cow::Owned(data))
+    } else {
+        Ok(Cow::Borrowed(raw))
+    }
+}
+
 /// 显示PAK文件简要信息
-pub fn show_pak_info_simple(data: &[u8], _is_encrypted: bool, files: &[FileInfo]) {
-    println!("  PAK 文件大小: {:.2} MB", data.len() as f64 / 1024.0 / 1024.0);
+pub fn show_pak_info_simple(file_size: u64, _is_encrypted: bool, files: &[FileInfo]) {
+    println!("  PAK 文件大小: {:.2} MB", file_size as f64 / 1024.0 / 1024.0);
     println!("  文件数量: {}", files.len());
 } 
\ No newline at end of file
@@ -0,0 +1,657 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use flate2::read::ZlibDecoder;
+use sha2::{Digest, Sha256};
+use crate::encoding::NameEncoding;
+use crate::error::PakError;
+use crate::hash::{to_hex, HashAlgorithm};
+use crate::pak::{write_pak_info, FileInfo, PakInfo};
+use crate::reader::{normalize_entry_name, PakReader};
+use crate::utils::{compress_data, write_file_atomically};
+use crate::writer::CryptWriter;
+
+/// 补丁文件开头的magic
+const PATCH_MAGIC: &[u8; 8] = b"PKTPATCH";
+
+/// 补丁格式的版本号
+const PATCH_FORMAT_VERSION: u32 = 1;
+
+/// 补丁中存储的条目数据使用的压缩等级
+const PATCH_COMPRESS_LEVEL: u32 = 9;
+
+/// 条目数据的来源：复用基础PAK中的条目，或者补丁中存储的数据
+///
+/// 存储的数据不放在内存中：刚制作的补丁记录它在修改后PAK中的序号，
+/// 读取的补丁记录它在补丁文件中的位置，写出或应用时再逐个条目读取。
+#[derive(Debug, Clone)]
+pub enum PatchSource {
+    /// 基础PAK中的条目序号（原始字节完全相同）
+    Base(usize),
+    /// 修改后PAK中的条目序号（制作补丁时，写出补丁时从修改后的PAK读取）
+    Modded(usize),
+    /// 补丁文件中zlib压缩的原始字节的偏移和长度（读取补丁时）
+    Stored { offset: u64, len: u32 },
+}
+
+impl PatchSource {
+    /// 数据是否存储在补丁中
+    pub fn is_stored(&self) -> bool {
+        !matches!(self, PatchSource::Base(_))
+    }
+}
+
+/// 目标PAK中的一个条目
+#[derive(Debug, Clone)]
+pub struct PatchEntry {
+    /// 条目名（由原始字节按补丁中的编码解码）
+    pub name: String,
+    /// 索引中的原始文件名字节
+    pub raw_name: Vec<u8>,
+    /// 文件时间戳（Windows FILETIME）
+    pub file_time: u64,
+    /// 数据大小（压缩模式下为压缩后的大小）
+    pub z_size: u32,
+    /// 原始大小（仅压缩模式下有意义）
+    pub size: u32,
+    pub source: PatchSource,
+}
+
+/// 增量补丁：从基础PAK生成修改后的PAK所需的全部信息
+///
+/// 二进制格式，整数均为小端序；文本为 u32 长度前缀的UTF-8，原始文件名为 u8 长度前缀的字节：
+///
+/// ```text
+/// "PKTPATCH"  u32 格式版本
+/// 基础PAK的 sha256  生成结果的 sha256  （十六进制文本）
+/// u32 版本号  u8 异或密钥（0 为不加密）  u8 是否压缩  编码名
+/// u32 删除的条目数，每个条目: 条目名（文本）
+/// u32 目标条目数，每个条目: 原始文件名  u64 FILETIME  u32 z_size  u32 size  u8 来源
+///     来源 0: u32 基础PAK中的条目序号
+///     来源 1: u32 长度  zlib压缩的原始字节
+/// ```
+#[derive(Debug, Clone)]
+pub struct PakPatch {
+    /// 基础PAK文件的 sha256
+    pub base_hash: String,
+    /// 应用补丁后生成的PAK文件的 sha256
+    pub target_hash: String,
+    /// 目标PAK头部中的版本号
+    pub version: u32,
+    /// 目标PAK的异或密钥，None 表示未加密
+    pub xor_key: Option<u8>,
+    /// 目标PAK是否为压缩模式
+    pub compressed: bool,
+    /// 目标PAK的文件名编码
+    pub encoding: NameEncoding,
+    /// 基础PAK中被删除的条目名（仅供显示）
+    pub removed: Vec<String>,
+    /// 目标PAK的条目（按目标中的顺序）
+    pub entries: Vec<PatchEntry>,
+}
+
+/// 补丁选项
+#[derive(Debug, Clone, Default)]
+pub struct PatchOptions {
+    /// 文件名编码（Auto 时自动识别）
+    pub encoding: NameEncoding,
+    /// 替换已存在的输出文件
+    pub force: bool,
+}
+
+impl PakPatch {
+    /// 比较基础PAK和修改后的PAK生成补丁
+    ///
+    /// 原始字节与基础PAK中某个条目完全相同的条目（优先同名条目，其次按内容查找）只记录序号，
+    /// 其余条目的数据存入补丁。
+    pub fn create<B: Read + Seek, M: Read + Seek>(
+        base: &mut PakReader<B>,
+        base_hash: String,
+        modded: &mut PakReader<M>,
+    ) -> io::Result<Self> {
+        let base_keys = (0..base.files().len())
+            .map(|index| Ok(content_key(&base.read_raw_entry(index)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut base_by_name = HashMap::new();
+        let mut base_by_content = HashMap::new();
+        for (index, key) in base_keys.iter().enumerate() {
+            base_by_name.entry(normalize_entry_name(&base.files()[index].file_name)).or_insert(index);
+            base_by_content.entry(key).or_insert(index);
+        }
+
+        let encoding = modded.encoding();
+        let mut entries = Vec::with_capacity(modded.files().len());
+        for index in 0..modded.files().len() {
+            let key = content_key(&modded.read_raw_entry(index)?);
+            let file_info = &modded.files()[index];
+
+            let same_name = base_by_name.get(&normalize_entry_name(&file_info.file_name)).copied()
+                .filter(|&base_index| base_keys[base_index] == key);
+            let source = match same_name.or_else(|| base_by_content.get(&key).copied()) {
+                Some(base_index) => PatchSource::Base(base_index),
+                None => PatchSource::Modded(index),
+            };
+
+            entries.push(PatchEntry {
+                name: file_info.file_name.clone(),
+                raw_name: match &file_info.raw_name {
+                    Some(raw_name) => raw_name.clone(),
+                    None => encoding.encode(&file_info.file_name)?,
+                },
+                file_time: file_info._file_time,
+                z_size: file_info.z_size,
+                size: file_info._size,
+                source,
+            });
+        }
+
+        let target_names: HashSet<_> = entries.iter()
+            .map(|entry| normalize_entry_name(&entry.name))
+            .collect();
+        let removed = base.files().iter()
+            .filter(|file_info| !target_names.contains(&normalize_entry_name(&file_info.file_name)))
+            .map(|file_info| file_info.file_name.clone())
+            .collect();
+
+        let mut patch = Self {
+            base_hash,
+            target_hash: String::new(),
+            version: modded.info().version,
+            xor_key: modded.variant().xor_key,
+            compressed: modded.is_compressed(),
+            encoding,
+            removed,
+            entries,
+        };
+        let mut sink = HashWriter::new(io::sink());
+        patch.write_target(base, &mut sink, &mut |entry, source, writer| {
+            copy_modded_entry(modded, entry, source, writer)
+        })?;
+        patch.target_hash = sink.finish();
+        Ok(patch)
+    }
+
+    /// 存入补丁的条目数量
+    pub fn stored_count(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.source.is_stored()).count()
+    }
+
+    /// 补丁中存储的条目数据总字节数（未经补丁压缩）
+    pub fn stored_bytes(&self) -> u64 {
+        self.entries.iter()
+            .filter(|entry| entry.source.is_stored())
+            .map(|entry| entry.z_size as u64)
+            .sum()
+    }
+
+    /// 用基础PAK和补丁文件中存储的数据生成目标PAK，并检查结果的校验和与补丁记录一致
+    ///
+    /// `patch_file` 是读取这个补丁时使用的文件。调用方需要先确认基础PAK的校验和与 `base_hash` 一致。
+    pub fn apply_to<R: Read + Seek, P: Read + Seek, W: Write>(
+        &self,
+        base: &mut PakReader<R>,
+        patch_file: &mut P,
+        writer: W,
+    ) -> io::Result<PakInfo> {
+        let mut writer = HashWriter::new(writer);
+        let pak_info = self.write_target(base, &mut writer, &mut |entry, source, writer| match source {
+            PatchSource::Stored { offset, len } => copy_stored_entry(patch_file, entry, *offset, *len, writer),
+            _ => Err(bad_patch(format!("{} 的数据不在补丁文件中", entry.name)).into()),
+        })?;
+        let hash = writer.finish();
+        if hash != self.target_hash {
+            return Err(bad_patch(format!("生成的PAK校验和为 {}，补丁记录为 {}", hash, self.target_hash)).into());
+        }
+        Ok(pak_info)
+    }
+
+    /// 写出补丁文件，存储的条目逐个从修改后的PAK读取并压缩
+    ///
+    /// `modded` 是制作这个补丁时使用的修改后的PAK。
+    pub fn write_to<M: Read + Seek, W: Write>(&self, modded: &mut PakReader<M>, writer: &mut W) -> io::Result<()> {
+        writer.write_all(PATCH_MAGIC)?;
+        writer.write_all(&PATCH_FORMAT_VERSION.to_le_bytes())?;
+        write_text(writer, &self.base_hash)?;
+        write_text(writer, &self.target_hash)?;
+        writer.write_all(&self.version.to_le_bytes())?;
+        writer.write_all(&[self.xor_key.unwrap_or(0), self.compressed as u8])?;
+        write_text(writer, &self.encoding.to_string())?;
+
+        writer.write_all(&count_u32(self.removed.len())?.to_le_bytes())?;
+        for name in &self.removed {
+            write_text(writer, name)?;
+        }
+
+        writer.write_all(&count_u32(self.entries.len())?.to_le_bytes())?;
+        for entry in &self.entries {
+            write_short_bytes(writer, &entry.raw_name)?;
+            writer.write_all(&entry.file_time.to_le_bytes())?;
+            writer.write_all(&entry.z_size.to_le_bytes())?;
+            writer.write_all(&entry.size.to_le_bytes())?;
+            match &entry.source {
+                PatchSource::Base(index) => {
+                    writer.write_all(&[0])?;
+                    writer.write_all(&count_u32(*index)?.to_le_bytes())?;
+                }
+                PatchSource::Modded(index) => {
+                    let packed = compress_data(&modded.read_raw_entry(*index)?, PATCH_COMPRESS_LEVEL)?;
+                    writer.write_all(&[1])?;
+                    writer.write_all(&count_u32(packed.len())?.to_le_bytes())?;
+                    writer.write_all(&packed)?;
+                }
+                PatchSource::Stored { .. } => {
+                    return Err(bad_patch(format!("{} 的数据在已有的补丁文件中，无法重新写出", entry.name)).into());
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    /// 读取补丁文件
+    ///
+    /// 存储的条目数据只记录位置，应用补丁时再从同一个 `reader` 读取和解压。
+    /// 所有长度都先和剩余的文件大小比较，不会按补丁中的数值预先分配内存。
+    pub fn read_from<R: Read + Seek>(reader: &mut R) -> Result<Self, PakError> {
+        let mut cursor = PatchCursor::new(reader)?;
+        if cursor.take(PATCH_MAGIC.len())? != PATCH_MAGIC {
+            return Err(bad_patch("不是pkt补丁文件".to_string()));
+        }
+        let format_version = cursor.u32()?;
+        if format_version != PATCH_FORMAT_VERSION {
+            return Err(bad_patch(format!("不支持的补丁格式版本: {}", format_version)));
+        }
+
+        let base_hash = cursor.text()?;
+        let target_hash = cursor.text()?;
+        let version = cursor.u32()?;
+        let xor_key = match cursor.u8()? {
+            0 => None,
+            key => Some(key),
+        };
+        let compressed = cursor.u8()? != 0;
+        let encoding: NameEncoding = cursor.text()?.parse().map_err(bad_patch)?;
+
+        let removed = (0..cursor.u32()?)
+            .map(|_| cursor.text())
+            .collect::<Result<Vec<_>, PakError>>()?;
+
+        let entry_count = cursor.u32()?;
+        let mut entries = Vec::new();
+        for _ in 0..entry_count {
+            let raw_name = cursor.short_bytes()?;
+            let file_time = cursor.u64()?;
+            let z_size = cursor.u32()?;
+            let size = cursor.u32()?;
+            let source = match cursor.u8()? {
+                0 => PatchSource::Base(cursor.u32()? as usize),
+                1 => {
+                    let len = cursor.u32()?;
+                    let offset = cursor.pos;
+                    cursor.skip(len as u64)?;
+                    PatchSource::Stored { offset, len }
+                }
+                other => return Err(bad_patch(format!("未知的条目来源: {}", other))),
+            };
+            entries.push(PatchEntry {
+                name: encoding.decode(&raw_name),
+                raw_name,
+                file_time,
+                z_size,
+                size,
+                source,
+            });
+        }
+        if cursor.pos != cursor.len {
+            return Err(bad_patch("条目之后有多余的数据".to_string()));
+        }
+
+        Ok(Self { base_hash, target_hash, version, xor_key, compressed, encoding, removed, entries })
+    }
+
+    /// 按补丁写出目标PAK：复用的条目从基础PAK复制原始字节，存储的条目交给 `write_stored` 写出
+    fn write_target<R: Read + Seek, W: Write>(
+        &self,
+        base: &mut PakReader<R>,
+        writer: W,
+        write_stored: &mut dyn FnMut(&PatchEntry, &PatchSource, &mut dyn Write) -> io::Result<()>,
+    ) -> io::Result<PakInfo> {
+        let mut writer = CryptWriter::new(writer, self.xor_key.unwrap_or(0));
+
+        let mut pak_info = PakInfo::new();
+        pak_info.version = self.version;
+        pak_info.compress = Some(self.compressed);
+        pak_info.encoding = self.encoding;
+        for entry in &self.entries {
+            pak_info.file_info_library.push(FileInfo {
+                file_name: entry.name.clone(),
+                z_size: entry.z_size,
+                _size: entry.size,
+                _file_time: entry.file_time,
+                raw_name: Some(entry.raw_name.clone()),
+            });
+        }
+        write_pak_info(&mut writer, &pak_info)?;
+
+        for entry in &self.entries {
+            match &entry.source {
+                PatchSource::Base(index) => {
+                    let raw = base.read_raw_entry(*index)?;
+                    if raw.len() != entry.z_size as usize {
+                        return Err(bad_patch(format!(
+                            "{} 对应基础PAK中的条目 #{}，但大小为 {} 字节，应为 {} 字节",
+                            entry.name, index, raw.len(), entry.z_size
+                        )).into());
+                    }
+                    writer.write_all(&raw)?;
+                }
+                source => write_stored(entry, source, &mut writer)?,
+            }
+        }
+
+        writer.flush()?;
+        Ok(pak_info)
+    }
+}
+
+/// 比较基础PAK和修改后的PAK，写出只包含新增和修改条目数据的补丁文件
+pub fn create_patch(base_path: &Path, modded_path: &Path, output_path: &Path, options: &PatchOptions) -> io::Result<PakPatch> {
+    if !options.force && output_path.exists() {
+        return Err(PakError::OutputExists { path: output_path.to_path_buf() }.into());
+    }
+    let base_hash = hash_file(base_path)?;
+    let modded_hash = hash_file(modded_path)?;
+    let mut base = PakReader::open_with_encoding(base_path, options.encoding)?;
+    let mut modded = PakReader::open_with_encoding(modded_path, options.encoding)?;
+
+    let patch = PakPatch::create(&mut base, base_hash, &mut modded)?;
+    write_file_atomically(output_path, options.force, |writer| patch.write_to(&mut modded, writer))?;
+
+    let base_names: HashSet<_> = base.files().iter()
+        .map(|file_info| normalize_entry_name(&file_info.file_name))
+        .collect();
+    let (mut added, mut modified) = (0, 0);
+    for entry in patch.entries.iter().filter(|entry| entry.source.is_stored()) {
+        if base_names.contains(&normalize_entry_name(&entry.name)) {
+            modified += 1;
+        } else {
+            added += 1;
+        }
+    }
+
+    println!("基础PAK: {}（{} 个条目）", base_path.display(), base.files().len());
+    println!("修改后: {}（{} 个条目）", modded_path.display(), modded.files().len());
+    println!(
+        "复用 {} 个条目，存储 {} 个条目（新增 {}，修改 {}，共 {} 字节），删除 {} 个条目",
+        patch.entries.len() - patch.stored_count(), patch.stored_count(), added, modified,
+        patch.stored_bytes(), patch.removed.len()
+    );
+    if patch.target_hash != modded_hash {
+        println!("警告: {} 中有补丁无法还原的部分（如数据区之后的多余数据），应用补丁得到的PAK与它不完全相同", modded_path.display());
+    }
+    println!("补丁已生成: {}（{} 字节）", output_path.display(), fs::metadata(output_path)?.len());
+    Ok(patch)
+}
+
+/// 检查基础PAK的校验和后应用补丁，生成修改后的PAK
+pub fn apply_patch(base_path: &Path, patch_path: &Path, output_path: &Path, options: &PatchOptions) -> io::Result<PakInfo> {
+    if !options.force && output_path.exists() {
+        return Err(PakError::OutputExists { path: output_path.to_path_buf() }.into());
+    }
+    if !patch_path.exists() {
+        return Err(PakError::InputNotFound { path: patch_path.to_path_buf() }.into());
+    }
+    let mut patch_file = BufReader::new(File::open(patch_path)?);
+    let patch = PakPatch::read_from(&mut patch_file)?;
+    let base_hash = hash_file(base_path)?;
+    if base_hash != patch.base_hash {
+        return Err(PakError::PatchBaseMismatch { expected: patch.base_hash, actual: base_hash }.into());
+    }
+
+    let mut base = PakReader::open_with_encoding(base_path, options.encoding)?;
+    let pak_info = write_file_atomically(output_path, options.force, |writer| {
+        patch.apply_to(&mut base, &mut patch_file, writer)
+    })?;
+
+    for name in &patch.removed {
+        println!("删除: {}", name);
+    }
+    println!(
+        "已生成: {}（{} 个条目，其中 {} 个来自补丁）",
+        output_path.display(), patch.entries.len(), patch.stored_count()
+    );
+    Ok(pak_info)
+}
+
+/// 整个文件的 sha256
+fn hash_file(path: &Path) -> io::Result<String> {
+    if !path.exists() {
+        return Err(PakError::InputNotFound { path: path.to_path_buf() }.into());
+    }
+    HashAlgorithm::Sha256.hash_reader(BufReader::new(File::open(path)?))
+}
+
+/// 从修改后的PAK复制一个存储条目的原始字节
+fn copy_modded_entry<M: Read + Seek>(
+    modded: &mut PakReader<M>,
+    entry: &PatchEntry,
+    source: &PatchSource,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    match source {
+        PatchSource::Modded(index) => writer.write_all(&modded.read_raw_entry(*index)?),
+        _ => Err(bad_patch(format!("{} 的数据不在修改后的PAK中", entry.name)).into()),
+    }
+}
+
+/// 从补丁文件解压一个存储条目的原始字节，最多解压 z_size + 1 字节
+fn copy_stored_entry<P: Read + Seek>(
+    patch_file: &mut P,
+    entry: &PatchEntry,
+    offset: u64,
+    len: u32,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    patch_file.seek(SeekFrom::Start(offset))?;
+    let mut decoder = ZlibDecoder::new(patch_file.take(len as u64)).take(entry.z_size as u64 + 1);
+    let mut buffer = [0u8; 64 * 1024];
+    let mut copied = 0u64;
+    loop {
+        let n = decoder.read(&mut buffer)
+            .map_err(|e| bad_patch(format!("{} 的数据解压失败: {}", entry.name, e)))?;
+        if n == 0 {
+            break;
+        }
+        copied += n as u64;
+        if copied > entry.z_size as u64 {
+            break;
+        }
+        writer.write_all(&buffer[..n])?;
+    }
+    if copied > entry.z_size as u64 {
+        return Err(bad_patch(format!("{} 的数据超过 {} 字节", entry.name, entry.z_size)).into());
+    }
+    if copied != entry.z_size as u64 {
+        return Err(bad_patch(format!("{} 的数据为 {} 字节，应为 {} 字节", entry.name, copied, entry.z_size)).into());
+    }
+    Ok(())
+}
+
+/// 用于比较条目原始字节的键
+fn content_key(raw: &[u8]) -> (usize, String) {
+    (raw.len(), HashAlgorithm::Sha256.hash_bytes(raw))
+}
+
+/// 边写入边计算 sha256 的包装器
+struct HashWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W> HashWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, hasher: Sha256::new() }
+    }
+
+    fn finish(self) -> String {
+        to_hex(&self.hasher.finalize())
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(data)?;
+        self.hasher.update(&data[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 按顺序读取补丁数据，读取前先检查剩余长度，越界时报告补丁被截断
+struct PatchCursor<'a, R> {
+    reader: &'a mut R,
+    pos: u64,
+    len: u64,
+}
+
+impl<'a, R: Read + Seek> PatchCursor<'a, R> {
+    fn new(reader: &'a mut R) -> io::Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        Ok(Self { reader, pos: 0, len })
+    }
+
+    fn check(&self, len: u64) -> Result<(), PakError> {
+        if len > self.len - self.pos {
+            return Err(bad_patch(format!("文件在偏移 {} 处被截断", self.pos)));
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, len: usize) -> Result<Vec<u8>, PakError> {
+        self.check(len as u64)?;
+        let mut bytes = vec![0u8; len];
+        self.reader.read_exact(&mut bytes)?;
+        self.pos += len as u64;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: u64) -> Result<(), PakError> {
+        self.check(len)?;
+        self.pos += len;
+        self.reader.seek(SeekFrom::Start(self.pos))?;
+        Ok(())
+    }
+
+    fn u8(&mut self) -> Result<u8, PakError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, PakError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, PakError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn short_bytes(&mut self) -> Result<Vec<u8>, PakError> {
+        let len = self.u8()? as usize;
+        self.take(len)
+    }
+
+    fn text(&mut self) -> Result<String, PakError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?).map_err(|_| bad_patch("字符串不是有效的UTF-8".to_string()))
+    }
+}
+
+fn write_short_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    let len = u8::try_from(bytes.len())
+        .map_err(|_| io::Error::from(bad_patch(format!("文件名过长（{} 字节，最多 255）", bytes.len()))))?;
+    writer.write_all(&[len])?;
+    writer.write_all(bytes)
+}
+
+fn write_text<W: Write>(writer: &mut W, text: &str) -> io::Result<()> {
+    writer.write_all(&count_u32(text.len())?.to_le_bytes())?;
+    writer.write_all(text.as_bytes())
+}
+
+fn count_u32(count: usize) -> io::Result<u32> {
+    u32::try_from(count).map_err(|_| bad_patch(format!("数量过大: {}", count)).into())
+}
+
+fn bad_patch(reason: String) -> PakError {
+    PakError::BadPatch { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::writer::PakWriter;
+
+    fn build_pak(entries: &[(&str, &[u8])], compression: Option<u32>, xor_key: Option<u8>) -> Vec<u8> {
+        let mut writer = PakWriter::new().compression(compression).xor_key(xor_key);
+        for (name, data) in entries {
+            writer.add_bytes(name, data.to_vec()).unwrap();
+        }
+        let mut bytes = Vec::new();
+        writer.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// 制作补丁并读回，返回补丁文件的内容和读取结果
+    fn make_patch(base: &[u8], modded: &[u8]) -> (Vec<u8>, PakPatch) {
+        let mut base = PakReader::new(Cursor::new(base.to_vec())).unwrap();
+        let mut modded = PakReader::new(Cursor::new(modded.to_vec())).unwrap();
+        let patch = PakPatch::create(&mut base, "base".to_string(), &mut modded).unwrap();
+        let mut patch_bytes = Vec::new();
+        patch.write_to(&mut modded, &mut patch_bytes).unwrap();
+        let read_back = PakPatch::read_from(&mut Cursor::new(patch_bytes.clone())).unwrap();
+        (patch_bytes, read_back)
+    }
+
+    #[test]
+    fn applying_a_created_patch_reproduces_the_modded_pak() {
+        for (compression, xor_key) in [(None, Some(0xF7)), (Some(6), None), (Some(9), Some(0x5A))] {
+            let base = build_pak(&[("a.txt", b"same"), ("b.txt", b"old"), ("c.txt", b"moved")], compression, xor_key);
+            let modded = build_pak(&[("a.txt", b"same"), ("b.txt", b"new data"), ("d\\c.txt", b"moved"), ("e.txt", b"added")], compression, xor_key);
+            let (patch_bytes, patch) = make_patch(&base, &modded);
+
+            assert_eq!(patch.stored_count(), 2);
+            assert_eq!(patch.removed, vec!["c.txt".to_string()]);
+            let mut output = Vec::new();
+            let mut base = PakReader::new(Cursor::new(base)).unwrap();
+            patch.apply_to(&mut base, &mut Cursor::new(patch_bytes), &mut output).unwrap();
+            assert_eq!(output, modded);
+        }
+    }
+
+    #[test]
+    fn stored_entry_larger_than_its_size_is_rejected() {
+        let base = build_pak(&[("a.txt", b"a")], None, None);
+        let modded = build_pak(&[("a.txt", &[0u8; 100_000])], None, None);
+        let (patch_bytes, mut patch) = make_patch(&base, &modded);
+        patch.entries[0].z_size = 10;
+
+        let mut base = PakReader::new(Cursor::new(base)).unwrap();
+        let err = patch.apply_to(&mut base, &mut Cursor::new(patch_bytes), io::sink()).unwrap_err();
+        assert!(matches!(PakError::from(err), PakError::BadPatch { reason } if reason.contains("超过 10 字节")));
+    }
+
+    #[test]
+    fn truncated_patch_is_rejected_without_reading_past_the_end() {
+        let base = build_pak(&[("a.txt", b"a")], None, None);
+        let modded = build_pak(&[("a.txt", b"changed")], None, None);
+        let (mut patch_bytes, _) = make_patch(&base, &modded);
+        patch_bytes.truncate(patch_bytes.len() - 3);
+
+        let err = PakPatch::read_from(&mut Cursor::new(patch_bytes)).unwrap_err();
+        assert!(matches!(err, PakError::BadPatch { reason } if reason.contains("被截断")));
+    }
+}
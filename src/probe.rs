@@ -0,0 +1,333 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use crate::error::{PakError, PakResult};
+use crate::pak::PakInfo;
+
+/// 探测时读取头部的初始缓冲区大小
+const PROBE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// PAK文件格式变体
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PakVariant {
+    /// 异或密钥，None 表示未加密
+    pub xor_key: Option<u8>,
+    /// 是否为压缩模式（索引中带原始大小字段，数据为zlib流）
+    pub compressed: bool,
+    /// 版本号
+    pub version: u32,
+}
+
+impl PakVariant {
+    /// 是否加密
+    pub fn is_encrypted(&self) -> bool {
+        self.xor_key.is_some()
+    }
+
+    /// 用于解密的密钥（未加密时为0）
+    pub fn key(&self) -> u8 {
+        self.xor_key.unwrap_or(0)
+    }
+}
+
+impl fmt::Display for PakVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.xor_key {
+            Some(key) => write!(f, "加密(0x{:02X})", key)?,
+            None => write!(f, "未加密")?,
+        }
+        let compress = if self.compressed { "压缩" } else { "未压缩" };
+        write!(f, " / {} / 版本 {}", compress, self.version)
+    }
+}
+
+/// 按某种布局遍历索引的结果
+#[derive(Debug, Clone, Copy)]
+enum Walk {
+    /// 索引完整：头部大小和数据区总大小
+    Complete { header_size: usize, data_size: u64 },
+    /// 缓冲区不足以读完索引
+    Truncated,
+    /// 该布局下索引无效
+    Invalid,
+}
+
+/// 按某种布局逐步遍历索引：缓冲区变大后从上次停下的记录继续，不重新遍历已读过的部分
+struct IndexWalk {
+    compressed: bool,
+    /// 下一条记录的位置
+    pos: usize,
+    /// 已遍历记录的数据大小之和
+    data_size: u64,
+}
+
+impl IndexWalk {
+    fn new(compressed: bool) -> Self {
+        Self { compressed, pos: 8, data_size: 0 }
+    }
+
+    /// 从上次停下的位置继续遍历，直到索引结束、无效或缓冲区不足
+    fn advance(&mut self, data: &[u8]) -> Walk {
+        let record_tail = if self.compressed { 4 + 4 + 8 } else { 4 + 8 };
+
+        loop {
+            let Some(&flag) = data.get(self.pos) else {
+                return Walk::Truncated;
+            };
+            if flag == PakInfo::INFO_END {
+                return Walk::Complete { header_size: self.pos + 1, data_size: self.data_size };
+            } else if flag != 0 {
+                return Walk::Invalid;
+            }
+
+            let Some(&name_len) = data.get(self.pos + 1) else {
+                return Walk::Truncated;
+            };
+            let tail = self.pos + 2 + name_len as usize;
+            if tail + record_tail > data.len() {
+                return Walk::Truncated;
+            }
+            let z_size = u32::from_le_bytes([data[tail], data[tail + 1], data[tail + 2], data[tail + 3]]);
+            self.data_size += z_size as u64;
+            self.pos = tail + record_tail;
+        }
+    }
+}
+
+/// 候选布局
+struct Candidate {
+    compressed: bool,
+    header_size: usize,
+    data_size: u64,
+}
+
+/// 探测PAK文件的格式变体
+///
+/// 密钥由 magic 确定（见 [`magic_key`]），对压缩和未压缩两种布局分别遍历整个索引，
+/// 检查数据区大小之和是否正好到达文件末尾，选出得分最高的布局。
+pub fn probe<R: Read + Seek>(reader: &mut R) -> PakResult<PakVariant> {
+    let (variant, _) = probe_header(reader)?;
+    Ok(variant)
+}
+
+/// 探测格式并返回解密后的头部数据（至少包含完整索引）
+///
+/// 最多读取 `PakInfo::MAX_INDEX_SIZE` 字节，超过时返回 `PakError::IndexTooLarge`。
+pub(crate) fn probe_header<R: Read + Seek>(reader: &mut R) -> PakResult<(PakVariant, Vec<u8>)> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    if file_len < 8 {
+        return Err(PakError::TruncatedIndex { offset: file_len });
+    }
+
+    let mut magic_bytes = [0u8; 4];
+    reader.read_exact(&mut magic_bytes)?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let Some(key) = magic_key(magic_bytes) else {
+        return Err(PakError::BadMagic { found: u32::from_le_bytes(magic_bytes) });
+    };
+
+    // 逐步扩大缓冲区（只解密新读入的部分），直到两种布局都能得出结论或到达索引长度上限
+    let limit = file_len.min(PakInfo::MAX_INDEX_SIZE as u64);
+    let mut data = Vec::new();
+    let mut walks = [IndexWalk::new(false), IndexWalk::new(true)];
+    let mut outcomes = [None; 2];
+    let mut chunk_size = PROBE_CHUNK_SIZE;
+    loop {
+        let start = data.len();
+        let to_read = (chunk_size as u64).min(limit - start as u64) as usize;
+        data.resize(start + to_read, 0);
+        reader.read_exact(&mut data[start..])?;
+        data[start..].iter_mut().for_each(|byte| *byte ^= key);
+        let at_limit = data.len() as u64 == limit;
+
+        for (walk, outcome) in walks.iter_mut().zip(outcomes.iter_mut()) {
+            if outcome.is_none() {
+                match walk.advance(&data) {
+                    Walk::Truncated if !at_limit => {}
+                    result => *outcome = Some(result),
+                }
+            }
+        }
+
+        if outcomes.iter().all(Option::is_some) {
+            break;
+        }
+        chunk_size *= 2;
+    }
+
+    let candidates: Vec<_> = walks.iter().zip(outcomes).filter_map(|(walk, outcome)| match outcome {
+        Some(Walk::Complete { header_size, data_size }) => Some(Candidate {
+            compressed: walk.compressed,
+            header_size,
+            data_size,
+        }),
+        _ => None,
+    }).collect();
+
+    // 为每个候选布局打分
+    let mut best: Option<(u32, &Candidate)> = None;
+    for candidate in &candidates {
+        let data_end = candidate.header_size as u64 + candidate.data_size;
+        let mut score = 1;
+        if data_end == file_len {
+            score += 4;
+        } else if data_end < file_len {
+            score += 1;
+        }
+        if candidate.compressed && looks_like_zlib(reader, candidate, key, file_len)? {
+            score += 2;
+        }
+        if best.as_ref().is_none_or(|(best_score, _)| score > *best_score) {
+            best = Some((score, candidate));
+        }
+    }
+
+    let Some((_, best)) = best else {
+        // 有布局读到缓冲区末尾仍未结束：文件还没读完时是索引太长，否则是文件被截断
+        let truncated = outcomes.iter().any(|outcome| matches!(outcome, Some(Walk::Truncated)));
+        return Err(match truncated {
+            true if limit < file_len => PakError::IndexTooLarge { limit },
+            true => PakError::TruncatedIndex { offset: file_len },
+            false => PakError::UnknownLayout,
+        });
+    };
+
+    data.truncate(best.header_size);
+    let version = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+
+    let variant = PakVariant {
+        xor_key: if key == 0 { None } else { Some(key) },
+        compressed: best.compressed,
+        version,
+    };
+    Ok((variant, data))
+}
+
+/// 检查数据开头是否为PAK magic（允许任意单字节异或密钥）
+pub fn has_pak_magic<R: Read>(reader: &mut R) -> io::Result<bool> {
+    let mut magic_bytes = [0u8; 4];
+    match reader.read_exact(&mut magic_bytes) {
+        Ok(()) => Ok(magic_key(magic_bytes).is_some()),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// 按文件头判断路径是否为PAK文件（不看扩展名）
+pub fn is_pak_file(path: &Path) -> bool {
+    path.is_file() && File::open(path).and_then(|mut file| has_pak_magic(&mut file)).unwrap_or(false)
+}
+
+/// 根据文件开头的magic推导异或密钥（0 表示未加密），不是PAK magic时返回 None
+///
+/// magic 的四个字节用同一个单字节密钥异或，因此密钥由第一个字节唯一确定。
+pub(crate) fn magic_key(magic_bytes: [u8; 4]) -> Option<u8> {
+    let expected = PakInfo::MAGIC.to_le_bytes();
+    let key = magic_bytes[0] ^ expected[0];
+    let consistent = magic_bytes.iter().zip(expected.iter()).all(|(byte, magic)| byte ^ key == *magic);
+    consistent.then_some(key)
+}
+
+/// 检查压缩候选布局下第一个条目的数据是否以zlib头开始
+fn looks_like_zlib<R: Read + Seek>(reader: &mut R, candidate: &Candidate, key: u8, file_len: u64) -> PakResult<bool> {
+    let offset = candidate.header_size as u64;
+    if offset + 2 > file_len || candidate.data_size < 2 {
+        return Ok(false);
+    }
+
+    let mut head = [0u8; 2];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut head)?;
+    let cmf = head[0] ^ key;
+    let flg = head[1] ^ key;
+
+    // zlib头：CM=8（deflate），且 (CMF*256 + FLG) 是31的倍数
+    Ok(cmf & 0x0F == 8 && (cmf as u16 * 256 + flg as u16).is_multiple_of(31))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::writer::PakWriter;
+
+    fn pak_bytes(key: Option<u8>, compress: Option<u32>) -> Vec<u8> {
+        let mut writer = PakWriter::new().xor_key(key).compression(compress);
+        writer.add_bytes("a.txt", b"hello hello hello".to_vec()).unwrap();
+        writer.add_bytes("dir\\b.txt", vec![7; 100]).unwrap();
+        let mut bytes = Vec::new();
+        writer.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn magic_key_is_derived_from_the_first_byte() {
+        let magic = PakInfo::MAGIC.to_le_bytes();
+        assert_eq!(magic_key(magic), Some(0));
+        assert_eq!(magic_key(magic.map(|byte| byte ^ 0xF7)), Some(0xF7));
+        assert_eq!(magic_key([magic[0], magic[1], magic[2], magic[3] ^ 1]), None);
+    }
+
+    #[test]
+    fn every_layout_is_detected() {
+        for key in [None, Some(0xF7), Some(0x21)] {
+            for compress in [None, Some(6)] {
+                let variant = probe(&mut Cursor::new(pak_bytes(key, compress))).unwrap();
+                assert_eq!(variant.xor_key, key);
+                assert_eq!(variant.compressed, compress.is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn header_contains_the_whole_index() {
+        let bytes = pak_bytes(Some(0xF7), None);
+        let (_, header) = probe_header(&mut Cursor::new(bytes)).unwrap();
+        let header_len = 8 + (2 + 5 + 12) + (2 + 9 + 12) + 1;
+        assert_eq!(header.len(), header_len);
+        assert_eq!(header.last(), Some(&PakInfo::INFO_END));
+    }
+
+    #[test]
+    fn index_spanning_several_chunks_is_walked_to_the_end() {
+        let mut writer = PakWriter::new();
+        for index in 0..5000 {
+            writer.add_bytes(&format!("particles\\effect_{:05}.xml", index), vec![b'x'; index % 7]).unwrap();
+        }
+        let mut bytes = Vec::new();
+        let info = writer.write_to(&mut bytes).unwrap();
+
+        let (variant, header) = probe_header(&mut Cursor::new(bytes)).unwrap();
+        assert!(header.len() > PROBE_CHUNK_SIZE);
+        assert_eq!(variant.xor_key, Some(0xF7));
+        assert_eq!(info.file_info_library.len(), 5000);
+    }
+
+    #[test]
+    fn truncated_index_is_reported() {
+        let bytes = pak_bytes(Some(0xF7), None);
+        for len in [8, 12, 30, 40] {
+            let error = probe_header(&mut Cursor::new(&bytes[..len])).unwrap_err();
+            assert!(matches!(error, PakError::TruncatedIndex { offset } if offset == len as u64), "len {}", len);
+        }
+        let error = probe_header(&mut Cursor::new(&bytes[..5])).unwrap_err();
+        assert!(matches!(error, PakError::TruncatedIndex { offset: 5 }));
+    }
+
+    #[test]
+    fn truncated_data_keeps_the_layout() {
+        let bytes = pak_bytes(None, Some(6));
+        let variant = probe(&mut Cursor::new(&bytes[..bytes.len() - 3])).unwrap();
+        assert!(variant.compressed);
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let error = probe_header(&mut Cursor::new(vec![0u8; 64])).unwrap_err();
+        assert!(matches!(error, PakError::BadMagic { found: 0 }));
+    }
+}
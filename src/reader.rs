@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use crate::encoding::NameEncoding;
+use crate::error::PakError;
+use crate::pak::{parse_pak_info, decode_entry_data, FileInfo, PakInfo};
+use crate::probe::{probe_header, PakVariant};
+
+/// 边读取边解密的包装器（对读到的每个字节异或密钥）
+pub struct CryptReader<R> {
+    inner: R,
+    key: u8,
+}
+
+impl<R> CryptReader<R> {
+    /// 创建包装器，key 为 0 时不做任何处理
+    pub fn new(inner: R, key: u8) -> Self {
+        Self { inner, key }
+    }
+
+    /// 取回内部读取器
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// 内部读取器的可变引用（读到的是未解密的数据）
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+}
+
+impl<R: Read> Read for CryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if self.key != 0 {
+            for byte in &mut buf[..n] {
+                *byte ^= self.key;
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for CryptReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// 基于 Read + Seek 的PAK读取器
+///
+/// 只解析文件头并计算每个条目的数据偏移，条目数据在需要时才读取和解密。
+pub struct PakReader<R> {
+    inner: CryptReader<R>,
+    info: PakInfo,
+    variant: PakVariant,
+    header_size: u64,
+    file_len: u64,
+    data_offsets: Vec<u64>,
+    name_index: HashMap<String, usize>,
+}
+
+impl PakReader<BufReader<File>> {
+    /// 打开PAK文件
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::open_with_encoding(path, NameEncoding::default())
+    }
+
+    /// 打开PAK文件，按指定编码解析文件名
+    pub fn open_with_encoding(path: &Path, encoding: NameEncoding) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::with_encoding(BufReader::new(file), encoding)
+    }
+}
+
+impl<R: Read + Seek> PakReader<R> {
+    /// 从任意 Read + Seek 创建读取器，探测格式并解析文件头
+    pub fn new(reader: R) -> io::Result<Self> {
+        Self::with_encoding(reader, NameEncoding::default())
+    }
+
+    /// 从任意 Read + Seek 创建读取器，按指定编码解析文件名（Auto 时自动识别）
+    pub fn with_encoding(mut reader: R, encoding: NameEncoding) -> io::Result<Self> {
+        // 探测格式变体，得到解密后的完整索引
+        let (variant, header) = probe_header(&mut reader)?;
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        let (info, header_size) = parse_pak_info(&header, variant.compressed, encoding)?;
+        let inner = CryptReader::new(reader, variant.key());
+
+        // 计算每个条目的数据偏移
+        let mut data_offsets = Vec::with_capacity(info.file_info_library.len());
+        let mut offset = header_size as u64;
+        for file_info in &info.file_info_library {
+            data_offsets.push(offset);
+            offset += file_info.z_size as u64;
+        }
+
+        // 建立条目名索引（不区分大小写，重名时以第一个为准）
+        let mut name_index = HashMap::with_capacity(info.file_info_library.len());
+        for (index, file_info) in info.file_info_library.iter().enumerate() {
+            name_index.entry(normalize_entry_name(&file_info.file_name)).or_insert(index);
+        }
+
+        Ok(Self {
+            inner,
+            info,
+            variant,
+            header_size: header_size as u64,
+            file_len,
+            data_offsets,
+            name_index,
+        })
+    }
+
+    /// PAK文件信息
+    pub fn info(&self) -> &PakInfo {
+        &self.info
+    }
+
+    /// 所有条目的文件信息
+    pub fn files(&self) -> &[FileInfo] {
+        &self.info.file_info_library
+    }
+
+    /// 文件名编码（Auto 时为识别出的编码）
+    pub fn encoding(&self) -> NameEncoding {
+        self.info.encoding
+    }
+
+    /// 探测到的格式变体
+    pub fn variant(&self) -> PakVariant {
+        self.variant
+    }
+
+    /// 是否加密
+    pub fn is_encrypted(&self) -> bool {
+        self.variant.is_encrypted()
+    }
+
+    /// 是否为压缩模式
+    pub fn is_compressed(&self) -> bool {
+        self.variant.compressed
+    }
+
+    /// 头部（索引）大小
+    pub fn header_size(&self) -> u64 {
+        self.header_size
+    }
+
+    /// PAK文件总大小
+    pub fn file_len(&self) -> u64 {
+        self.file_len
+    }
+
+    /// 指定条目的数据在PAK文件中的偏移
+    pub fn data_offset(&self, index: usize) -> Option<u64> {
+        self.data_offsets.get(index).copied()
+    }
+
+    /// 按名称查找条目索引（不区分大小写，正反斜杠均可）
+    pub fn find_entry(&self, name: &str) -> Option<usize> {
+        self.name_index.get(&normalize_entry_name(name)).copied()
+    }
+
+    /// 按名称获取条目信息
+    pub fn entry(&self, name: &str) -> Option<&FileInfo> {
+        self.find_entry(name).map(|index| &self.info.file_info_library[index])
+    }
+
+    /// 按名称打开条目，返回只覆盖该条目内容的读取器
+    pub fn open_entry(&mut self, name: &str) -> io::Result<EntryReader<'_, R>> {
+        let index = self.find_entry(name).ok_or_else(|| PakError::EntryNotFound {
+            name: name.to_string(),
+        })?;
+        self.open_entry_by_index(index)
+    }
+
+    /// 按索引打开条目
+    ///
+    /// 未压缩的条目直接在PAK文件上按偏移读取；压缩条目会先整体解压到内存。
+    pub fn open_entry_by_index(&mut self, index: usize) -> io::Result<EntryReader<'_, R>> {
+        if self.is_compressed() {
+            let data = self.read_entry(index)?;
+            return Ok(EntryReader {
+                source: EntrySource::Inflated(Cursor::new(data)),
+            });
+        }
+
+        let (start, file_info) = self.entry_bounds(index)?;
+        let len = file_info.z_size as u64;
+        self.inner.seek(SeekFrom::Start(start))?;
+        Ok(EntryReader {
+            source: EntrySource::Stored {
+                inner: &mut self.inner,
+                start,
+                len,
+                pos: 0,
+            },
+        })
+    }
+
+    /// 读取条目的原始数据（已解密，压缩模式下未解压）
+    pub fn read_raw_entry(&mut self, index: usize) -> io::Result<Vec<u8>> {
+        let (offset, file_info) = self.entry_bounds(index)?;
+        let mut data = vec![0u8; file_info.z_size as usize];
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// 读取条目内容（压缩模式下自动解压）
+    pub fn read_entry(&mut self, index: usize) -> io::Result<Vec<u8>> {
+        let raw = self.read_raw_entry(index)?;
+        let file_info = &self.info.file_info_library[index];
+        let data = decode_entry_data(&raw, file_info, self.is_compressed())?;
+        Ok(data.into_owned())
+    }
+
+    /// 取回内部读取器
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+
+    /// 内部读取器的可变引用（读到的是未解密的原始数据，每次读取条目前都会重新定位）
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+
+    /// 校验条目索引和数据边界，返回数据偏移
+    fn entry_bounds(&self, index: usize) -> io::Result<(u64, &FileInfo)> {
+        let file_info = self.info.file_info_library.get(index).ok_or_else(|| PakError::EntryNotFound {
+            name: format!("#{}", index),
+        })?;
+        let offset = self.data_offsets[index];
+
+        if offset + file_info.z_size as u64 > self.file_len {
+            return Err(PakError::EntryOutOfBounds {
+                name: file_info.file_name.clone(),
+                offset,
+                size: file_info.z_size,
+                file_len: self.file_len,
+            }.into());
+        }
+
+        Ok((offset, file_info))
+    }
+}
+
+/// 单个条目的读取器（Read + Seek），位置相对于条目内容开头
+pub struct EntryReader<'a, R> {
+    source: EntrySource<'a, R>,
+}
+
+enum EntrySource<'a, R> {
+    /// 未压缩条目：直接读取PAK中的数据区间
+    Stored {
+        inner: &'a mut CryptReader<R>,
+        start: u64,
+        len: u64,
+        pos: u64,
+    },
+    /// 压缩条目：已解压到内存
+    Inflated(Cursor<Vec<u8>>),
+}
+
+impl<R> EntryReader<'_, R> {
+    /// 条目内容的长度（解压后）
+    pub fn len(&self) -> u64 {
+        match &self.source {
+            EntrySource::Stored { len, .. } => *len,
+            EntrySource::Inflated(cursor) => cursor.get_ref().len() as u64,
+        }
+    }
+
+    /// 条目内容是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<R: Read + Seek> Read for EntryReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.source {
+            EntrySource::Stored { inner, len, pos, .. } => {
+                let remaining = len.saturating_sub(*pos);
+                let to_read = (buf.len() as u64).min(remaining) as usize;
+                if to_read == 0 {
+                    return Ok(0);
+                }
+                let n = inner.read(&mut buf[..to_read])?;
+                *pos += n as u64;
+                Ok(n)
+            }
+            EntrySource::Inflated(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for EntryReader<'_, R> {
+    fn seek(&mut self, target: SeekFrom) -> io::Result<u64> {
+        match &mut self.source {
+            EntrySource::Stored { inner, start, len, pos } => {
+                let new_pos = match target {
+                    SeekFrom::Start(offset) => Some(offset),
+                    SeekFrom::End(offset) => len.checked_add_signed(offset),
+                    SeekFrom::Current(offset) => pos.checked_add_signed(offset),
+                };
+                let new_pos = new_pos.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "无效的定位位置")
+                })?;
+                // 超出条目末尾时读取返回0，与文件的行为一致
+                inner.seek(SeekFrom::Start(*start + new_pos.min(*len)))?;
+                *pos = new_pos;
+                Ok(new_pos)
+            }
+            EntrySource::Inflated(cursor) => cursor.seek(target),
+        }
+    }
+}
+
+/// 规范化条目名用于查找（统一为反斜杠、去掉开头的分隔符、转为小写）
+pub fn normalize_entry_name(name: &str) -> String {
+    name.replace('/', "\\").trim_start_matches('\\').to_lowercase()
+}
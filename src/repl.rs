@@ -1,10 +1,20 @@
-use std::fs::{self, File};
-use std::io::{self, Write};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read, Seek, Write};
 use std::path::Path;
 use colored::*;
 use regex::Regex;
-use crate::pak::{parse_pak_info, FileInfo, show_pak_info_simple};
-use crate::utils::{crypt_data, ensure_directory_exists};
+use crate::pak::{FileInfo, show_pak_info_simple};
+use crate::encoding::NameEncoding;
+use crate::entry_path::{sanitize_entry_path, PathLayout};
+use crate::error::PakError;
+use crate::hash::{hash_entry, HashAlgorithm};
+use crate::filter::{filter_files_by_name, filter_files_by_pattern, filter_files_by_regex, matches_glob_pattern};
+use crate::overwrite::{write_file_with_policy, OverwritePolicy, WriteOutcome, WriteStats};
+use crate::probe::PakVariant;
+use crate::reader::PakReader;
+use crate::utils::{ensure_directory_exists, format_filetime};
+use crate::verify::{format_verify_report, verify_pak};
 
 /// 输出重定向目标
 enum OutputTarget {
@@ -56,7 +66,7 @@ fn format_file_info_with_captures(file: &FileInfo, format_str: Option<&str>, cap
     
     // 提取文件信息，转换为Unix风格路径用于显示
     let full_path_unix = file.file_name.replace('\\', "/");
-    let file_name = full_path_unix.split('/').last().unwrap_or(&full_path_unix);
+    let file_name = full_path_unix.split('/').next_back().unwrap_or(&full_path_unix);
     let dir_path = if let Some(pos) = full_path_unix.rfind('/') {
         &full_path_unix[..pos]
     } else {
@@ -83,7 +93,8 @@ fn format_file_info_with_captures(file: &FileInfo, format_str: Option<&str>, cap
         .replace("$name", file_name)
         .replace("$dir", dir_path)
         .replace("$size", &file.z_size.to_string())
-        .replace("$osize", &file._size.to_string());
+        .replace("$osize", &file._size.to_string())
+        .replace("$time", &format_filetime(file._file_time));
     
     format
 }
@@ -100,7 +111,7 @@ fn format_dir_info_with_captures(dir_path: &str, format_str: Option<&str>, captu
     
     // 转换为Unix风格路径用于显示
     let dir_path_unix = dir_path.replace('\\', "/");
-    let dir_name = dir_path_unix.split('/').last().unwrap_or(&dir_path_unix);
+    let dir_name = dir_path_unix.split('/').next_back().unwrap_or(&dir_path_unix);
     let parent_path = if let Some(pos) = dir_path_unix.rfind('/') {
         &dir_path_unix[..pos]
     } else {
@@ -127,7 +138,9 @@ fn format_dir_info_with_captures(dir_path: &str, format_str: Option<&str>, captu
         .replace("$name", dir_name)
         .replace("$dir", parent_path)
         .replace("$size", "<DIR>")
-        .replace("$osize", "<DIR>");
+        .replace("$osize", "<DIR>")
+        .replace("$time", "<DIR>")
+        .replace("$hash", "<DIR>");
     
     format
 }
@@ -148,9 +161,7 @@ fn parse_command_args(input: &str) -> Vec<String> {
     let mut args = Vec::new();
     let mut current_arg = String::new();
     let mut in_quotes = false;
-    let mut chars = input.chars().peekable();
-    
-    while let Some(ch) = chars.next() {
+    for ch in input.chars() {
         match ch {
             '"' => {
                 in_quotes = !in_quotes;
@@ -295,11 +306,7 @@ impl PakFileSystem {
                     let remaining = &file_path[normalized_prefix.len()..];
                     
                     // 如果剩余路径以 \ 开头，去掉它
-                    let remaining = if remaining.starts_with('\\') {
-                        &remaining[1..]
-                    } else {
-                        remaining
-                    };
+                    let remaining = remaining.strip_prefix('\\').unwrap_or(remaining);
                     
                     if let Some(slash_pos) = remaining.find('\\') {
                         // 这是一个子目录中的文件
@@ -316,13 +323,13 @@ impl PakFileSystem {
         }
         
         // 按字母顺序排序，不区分大小写
-        directories.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()));
+        directories.sort_by_key(|a| a.to_lowercase());
         
         // 将文件按名称排序，不区分大小写
         let mut sorted_files: Vec<&FileInfo> = files;
         sorted_files.sort_by(|a, b| {
-            let name_a = a.file_name.split('\\').last().unwrap_or(&a.file_name).to_lowercase();
-            let name_b = b.file_name.split('\\').last().unwrap_or(&b.file_name).to_lowercase();
+            let name_a = a.file_name.split('\\').next_back().unwrap_or(&a.file_name).to_lowercase();
+            let name_b = b.file_name.split('\\').next_back().unwrap_or(&b.file_name).to_lowercase();
             name_a.cmp(&name_b)
         });
         
@@ -377,21 +384,16 @@ impl PakFileSystem {
 
 /// 批处理模式：执行命令列表后退出
 pub fn run_batch_commands(pak_path: &Path, commands: &[String]) -> io::Result<()> {
-    // 读取并解析PAK文件
-    let mut data = fs::read(pak_path)?;
-    
-    // 检测是否加密
-    let encrypted = detect_encryption(&data);
-    
-    if encrypted {
-        crypt_data(&mut data);
-    }
-    
-    // 解析PAK信息
-    let (pak_info, _header_size) = parse_pak_info(&data)?;
+    run_batch_commands_with_encoding(pak_path, commands, NameEncoding::default())
+}
+
+/// 批处理模式（按指定编码解析文件名）
+pub fn run_batch_commands_with_encoding(pak_path: &Path, commands: &[String], encoding: NameEncoding) -> io::Result<()> {
+    // 打开PAK文件（只解析头部）
+    let mut reader = PakReader::open_with_encoding(pak_path, encoding)?;
     
     // 创建文件系统
-    let mut fs = PakFileSystem::new(pak_info.file_info_library);
+    let mut fs = PakFileSystem::new(reader.files().to_vec());
     
     // 依次执行每个命令
     for (index, command_str) in commands.iter().enumerate() {
@@ -406,7 +408,7 @@ pub fn run_batch_commands(pak_path: &Path, commands: &[String]) -> io::Result<()
         let mut output = OutputBuffer::new();
         
         // 执行命令（复用REPL中的命令处理逻辑）
-        let result = execute_command(&mut fs, &data, encrypted, &parts, &mut output);
+        let result = execute_command(&mut fs, &mut reader, &parts, &mut output);
         
         // 输出结果
         if let Err(e) = result {
@@ -421,15 +423,33 @@ pub fn run_batch_commands(pak_path: &Path, commands: &[String]) -> io::Result<()
     Ok(())
 }
 
+/// 列出PAK中指定目录的内容（path 为 None 时列出根目录）
+pub fn list_pak_directory(pak_path: &Path, path: Option<&str>, long_format: bool, encoding: NameEncoding) -> io::Result<()> {
+    let reader = PakReader::open_with_encoding(pak_path, encoding)?;
+    let fs = PakFileSystem::new(reader.files().to_vec());
+
+    let mut output = OutputBuffer::new();
+    list_directory_to_buffer(&fs, path.unwrap_or(""), long_format, &mut output);
+    output.flush_to(&OutputTarget::Stdout)
+}
+
+/// 显示PAK文件信息
+pub fn show_pak_info(pak_path: &Path, encoding: NameEncoding) -> io::Result<()> {
+    let reader = PakReader::open_with_encoding(pak_path, encoding)?;
+
+    let mut output = OutputBuffer::new();
+    show_pak_info_to_buffer(reader.file_len(), reader.variant(), reader.encoding(), reader.files(), &mut output);
+    output.flush_to(&OutputTarget::Stdout)
+}
+
 /// 执行单个命令
-fn execute_command(
+fn execute_command<R: Read + Seek>(
     fs: &mut PakFileSystem,
-    data: &[u8],
-    encrypted: bool,
+    reader: &mut PakReader<R>,
     parts: &[String],
     output: &mut OutputBuffer
 ) -> io::Result<()> {
-    let command = parts.get(0).map(|s| s.as_str()).unwrap_or("");
+    let command = parts.first().map(|s| s.as_str()).unwrap_or("");
     
     match command {
         "help" | "h" => {
@@ -437,13 +457,14 @@ fn execute_command(
             Ok(())
         },
         "ls" | "dir" => {
-            let target_path = if parts.len() > 1 {
-                &parts[1]
-            } else {
-                ""
-            };
+            // -l 显示修改时间和大小
+            let long_format = parts.iter().skip(1).any(|arg| arg == "-l");
+            let target_path = parts.iter().skip(1)
+                .find(|arg| *arg != "-l")
+                .map(|s| s.as_str())
+                .unwrap_or("");
             
-            list_directory_to_buffer(fs, target_path, output);
+            list_directory_to_buffer(fs, target_path, long_format, output);
             Ok(())
         },
         "cd" => {
@@ -461,10 +482,17 @@ fn execute_command(
             }
         },
         "find" => {
-            execute_find_command(fs, data, parts, output)
+            execute_find_command(fs, reader, parts, output)
         },
         "info" => {
-            show_pak_info_to_buffer(data, encrypted, &fs.files, output);
+            show_pak_info_to_buffer(reader.file_len(), reader.variant(), reader.encoding(), &fs.files, output);
+            Ok(())
+        },
+        "verify" => {
+            let report = verify_pak(reader)?;
+            for line in format_verify_report(&report) {
+                output.writeln(line);
+            }
             Ok(())
         },
         _ => {
@@ -475,9 +503,9 @@ fn execute_command(
 }
 
 /// 执行find命令
-fn execute_find_command(
+fn execute_find_command<R: Read + Seek>(
     fs: &PakFileSystem,
-    data: &[u8],
+    reader: &mut PakReader<R>,
     parts: &[String],
     output: &mut OutputBuffer
 ) -> io::Result<()> {
@@ -487,6 +515,10 @@ fn execute_find_command(
     let mut show_help = false;
     let mut parse_error = false;
     let mut extract_dir = None;
+    let mut extract_template = None;
+    let mut layout = PathLayout::Full;
+    let mut overwrite = None;
+    let mut hash_algorithm = HashAlgorithm::default();
     
     // 解析find命令参数
     let mut i = 1;
@@ -549,6 +581,66 @@ fn execute_find_command(
                     break;
                 }
             },
+            "-extract-as" => {
+                if i + 1 < parts.len() {
+                    extract_template = Some(parts[i + 1].as_str());
+                    i += 2;
+                } else {
+                    output.writeln(format!("{}", "错误: -extract-as 需要指定路径模板".red()));
+                    parse_error = true;
+                    break;
+                }
+            },
+            "-strip-components" => {
+                match parts.get(i + 1).and_then(|count| count.parse::<usize>().ok()) {
+                    Some(count) => {
+                        layout = PathLayout::StripComponents(count);
+                        i += 2;
+                    }
+                    None => {
+                        output.writeln(format!("{}", "错误: -strip-components 需要指定目录层数".red()));
+                        parse_error = true;
+                        break;
+                    }
+                }
+            },
+            "-flatten" => {
+                layout = PathLayout::Flatten;
+                i += 1;
+            },
+            "-algo" => {
+                match parts.get(i + 1).map(|name| name.parse::<HashAlgorithm>()) {
+                    Some(Ok(algorithm)) => {
+                        hash_algorithm = algorithm;
+                        i += 2;
+                    }
+                    Some(Err(e)) => {
+                        output.writeln(format!("{}", format!("错误: {}", e).red()));
+                        parse_error = true;
+                        break;
+                    }
+                    None => {
+                        output.writeln(format!("{}", "错误: -algo 需要指定校验算法".red()));
+                        parse_error = true;
+                        break;
+                    }
+                }
+            },
+            "-force" | "-skip-existing" | "-update" | "-backup" => {
+                let policy = match parts[i].as_str() {
+                    "-force" => OverwritePolicy::Force,
+                    "-skip-existing" => OverwritePolicy::SkipExisting,
+                    "-update" => OverwritePolicy::Update,
+                    _ => OverwritePolicy::Backup,
+                };
+                if overwrite.is_some_and(|existing| existing != policy) {
+                    output.writeln(format!("{}", "错误: -force、-skip-existing、-update、-backup 只能指定一个".red()));
+                    parse_error = true;
+                    break;
+                }
+                overwrite = Some(policy);
+                i += 1;
+            },
             _ => {
                 output.writeln(format!("{}", format!("未知参数: {}", &parts[i]).red()));
                 parse_error = true;
@@ -557,16 +649,36 @@ fn execute_find_command(
         }
     }
     
+    if !parse_error && extract_template.is_some() && layout != PathLayout::Full {
+        output.writeln(format!("{}", "错误: -extract-as 不能与 -strip-components 或 -flatten 同时使用".red()));
+        parse_error = true;
+    }
+    
     // 根据解析结果执行相应操作
     if show_help {
         show_find_help(output);
     } else if parse_error {
         // 参数解析错误，错误信息已经输出
-    } else if let Some(extract_path) = extract_dir {
-        // 提取模式：将筛选的文件解包到指定目录
-        match extract_filtered_files(fs, data, extract_path, search_type, search_value.map(|s| s.as_str())) {
-            Ok(count) => {
-                output.writeln(format!("{}", format!("成功提取 {} 个文件到: {}", count, extract_path).green()));
+    } else if extract_dir.is_some() || extract_template.is_some() {
+        // 提取模式：将筛选的文件解包到指定目录（-extract-as 未指定目录时为当前目录）
+        let extract_path = extract_dir.map(|dir| dir.as_str()).unwrap_or(".");
+        let extract = ExtractOptions {
+            dir: extract_path,
+            template: extract_template,
+            layout,
+            // 与解包相同，未指定覆盖策略时不覆盖任何已存在的文件
+            policy: overwrite.unwrap_or_default(),
+            hash_algorithm,
+        };
+        match extract_filtered_files(fs, reader, search_type, search_value.map(|s| s.as_str()), &extract, output) {
+            Ok(stats) => {
+                output.writeln(format!("{}", format!("成功提取 {} 个文件到: {}", stats.written(), extract_path).green()));
+                if stats.overwritten + stats.backed_up + stats.skipped > 0 {
+                    output.writeln(format!(
+                        "新建 {}，覆盖 {}，备份后覆盖 {}，跳过已存在 {}",
+                        stats.created, stats.overwritten, stats.backed_up, stats.skipped
+                    ));
+                }
             }
             Err(e) => {
                 output.writeln(format!("{}", format!("提取失败: {}", e).red()));
@@ -574,25 +686,26 @@ fn execute_find_command(
         }
     } else {
         // 执行find命令
+        let mut hasher = FormatHasher { reader, algorithm: hash_algorithm };
         match search_type {
             Some("name") => {
                 if let Some(filename) = search_value {
-                    find_by_name_to_buffer_with_format(fs, filename.as_str(), format_str.map(|s| s.as_str()), output);
+                    find_by_name_to_buffer_with_format(fs, filename.as_str(), format_str.map(|s| s.as_str()), &mut hasher, output);
                 }
             },
             Some("filter") => {
                 if let Some(pattern) = search_value {
-                    find_by_pattern_to_buffer_with_format(fs, pattern.as_str(), format_str.map(|s| s.as_str()), output);
+                    find_by_pattern_to_buffer_with_format(fs, pattern.as_str(), format_str.map(|s| s.as_str()), &mut hasher, output);
                 }
             },
             Some("match") => {
                 if let Some(regex_pattern) = search_value {
-                    find_by_regex_to_buffer_with_format(fs, regex_pattern.as_str(), format_str.map(|s| s.as_str()), output);
+                    find_by_regex_to_buffer_with_format(fs, regex_pattern.as_str(), format_str.map(|s| s.as_str()), &mut hasher, output);
                 }
             },
             None => {
                 // 没有搜索条件，列出当前目录所有文件
-                find_all_files_in_path_to_buffer_with_format(fs, &fs.current_path, format_str.map(|s| s.as_str()), output);
+                find_all_files_in_path_to_buffer_with_format(fs, &fs.current_path, format_str.map(|s| s.as_str()), &mut hasher, output);
             },
             _ => {
                 output.writeln("用法:".to_string());
@@ -606,6 +719,8 @@ fn execute_find_command(
                 output.writeln("  $dir    - 目录路径".to_string());
                 output.writeln("  $size   - 文件大小（压缩后）".to_string());
                 output.writeln("  $osize  - 原始文件大小".to_string());
+                output.writeln("  $time   - 文件修改时间".to_string());
+                output.writeln("  $hash   - 文件内容的校验和（-algo 指定算法，默认 sha256）".to_string());
                 output.writeln("示例: find -format \"$path -- $size bytes\"".to_string());
             }
         }
@@ -616,26 +731,23 @@ fn execute_find_command(
 
 /// 运行交互式REPL模式
 pub fn run_repl(pak_path: &Path) -> io::Result<()> {
+    run_repl_with_encoding(pak_path, NameEncoding::default())
+}
+
+/// 运行交互式REPL模式（按指定编码解析文件名）
+pub fn run_repl_with_encoding(pak_path: &Path, encoding: NameEncoding) -> io::Result<()> {
     println!("进入交互模式...");
     println!("正在加载PAK文件: {}", pak_path.display());
     
-    // 读取和解析PAK文件
-    let mut data = fs::read(pak_path)?;
-    
-    // 检测是否加密
-    let encrypted = detect_encryption(&data);
-    if encrypted {
-        crypt_data(&mut data);
-    }
-    
-    let (pak_info, _) = parse_pak_info(&data)?;
+    // 打开PAK文件（只解析头部）
+    let mut reader = PakReader::open_with_encoding(pak_path, encoding)?;
     
     println!();
     println!("PAK 文件信息:");
-    show_pak_info_simple(&data, encrypted, &pak_info.file_info_library);
+    show_pak_info_simple(reader.file_len(), reader.is_encrypted(), reader.files());
     println!();
     
-    let mut fs = PakFileSystem::new(pak_info.file_info_library);
+    let mut fs = PakFileSystem::new(reader.files().to_vec());
     
     println!("交互式PAK浏览器");
     println!("输入 'help' 查看可用命令，'exit' 退出程序");
@@ -657,7 +769,7 @@ pub fn run_repl(pak_path: &Path) -> io::Result<()> {
                 // 解析命令和重定向
                 let (command_line, output_target) = parse_command_line(input);
                 let parts = parse_command_args(&command_line);
-                let command = parts.get(0).map(|s| s.as_str()).unwrap_or("");
+                let command = parts.first().map(|s| s.as_str()).unwrap_or("");
                 
                 // 创建输出缓冲区
                 let mut output = OutputBuffer::new();
@@ -668,7 +780,7 @@ pub fn run_repl(pak_path: &Path) -> io::Result<()> {
                         break;
                     },
                     _ => {
-                        execute_command(&mut fs, &data, encrypted, &parts, &mut output)
+                        execute_command(&mut fs, &mut reader, &parts, &mut output)
                     }
                 };
                 
@@ -697,6 +809,7 @@ fn show_help_to_buffer(output: &mut OutputBuffer) {
     output.writeln(format!("{}", "可用命令:".bright_cyan().bold()));
     output.writeln(format!("  {}                  显示此帮助信息", "help, h".bright_green()));
     output.writeln(format!("  {}                列出目录内容 (支持相对/绝对路径)", "ls [path]".bright_green()));
+    output.writeln(format!("  {}             列出目录内容，显示修改时间和大小", "ls -l [path]".bright_green()));
     output.writeln(format!("  {}                切换目录 (支持 .., ./, ../, /abs/path, rel/path)", "cd <path>".bright_green()));
     output.writeln(format!("  {}                     列出当前目录下所有文件", "find".bright_green()));
     output.writeln(format!("  {}               显示find命令详细帮助", "find -help".bright_green()));
@@ -706,6 +819,7 @@ fn show_help_to_buffer(output: &mut OutputBuffer) {
     output.writeln(format!("    支持通配符: {}", "* ? [abc] [a-z] [!abc]".yellow()));
     output.writeln(format!("    示例: {} 或 {}", "find -filter /compiled/*".yellow(), "find -filter *.jpg".yellow()));
     output.writeln(format!("  {}                     显示PAK文件信息", "info".bright_green()));
+    output.writeln(format!("  {}                   校验索引和所有条目，列出全部问题", "verify".bright_green()));
     output.writeln(format!("  {}            退出程序", "exit, quit, q".bright_green()));
     output.writeln(format!("  {}     重定向输出到文件", "[command] > file.txt".yellow()));
 }
@@ -724,6 +838,14 @@ fn show_find_help(output: &mut OutputBuffer) {
     output.writeln(format!("  {}         按正则表达式查找", "-match <正则表达式>".bright_green()));
     output.writeln(format!("  {}     自定义输出格式", "-format <格式字符串>".bright_green()));
     output.writeln(format!("  {}       将筛选的文件解包到指定目录", "-extract <目录>".bright_green()));
+    output.writeln(format!("  {}    按模板生成输出路径（可用格式变量和捕获组）", "-extract-as <路径模板>".bright_green()));
+    output.writeln(format!("  {}  提取时去掉条目路径开头的 N 层目录", "-strip-components <N>".bright_green()));
+    output.writeln(format!("  {}              提取时只保留文件名", "-flatten".bright_green()));
+    output.writeln(format!("  {}         $hash 使用的校验算法: crc32、md5、sha1、sha256（默认）", "-algo <算法>".bright_green()));
+    output.writeln(format!("  {}               提取时覆盖已存在的文件", "-force".bright_green()));
+    output.writeln(format!("  {}       提取时保留已存在的文件", "-skip-existing".bright_green()));
+    output.writeln(format!("  {}              提取时只覆盖大小或修改时间不同的文件", "-update".bright_green()));
+    output.writeln(format!("  {}              提取时先把已存在的文件改名为 .bak 再覆盖", "-backup".bright_green()));
     output.writeln("".to_string());
     output.writeln(format!("{}", "通配符:".bright_cyan()));
     output.writeln(format!("  {}              匹配任意数量的字符", "*".yellow()));
@@ -751,6 +873,8 @@ fn show_find_help(output: &mut OutputBuffer) {
     output.writeln(format!("  {}           文件所在目录路径", "$dir".magenta()));
     output.writeln(format!("  {}          文件大小（压缩后，字节）", "$size".magenta()));
     output.writeln(format!("  {}         原始文件大小（字节）", "$osize".magenta()));
+    output.writeln(format!("  {}          文件修改时间（UTC）", "$time".magenta()));
+    output.writeln(format!("  {}          文件内容的校验和（需要读取条目，算法见 -algo）", "$hash".magenta()));
     output.writeln(format!("  {}           正则表达式完整匹配", "$0".magenta()));
     output.writeln(format!("  {}           正则表达式第1个捕获组", "$1".magenta()));
     output.writeln(format!("  {}           正则表达式第2个捕获组", "$2".magenta()));
@@ -792,6 +916,9 @@ fn show_find_help(output: &mut OutputBuffer) {
     output.writeln(format!("   {}                    # 提取所有ogg文件", "find -filter \"*.ogg\" -extract sounds".yellow()));
     output.writeln(format!("   {}             # 提取compiled目录", "find -match \"^compiled/\" -extract output".yellow()));
     output.writeln(format!("   {}    # 提取特定文件", "find -name \"config.xml\" -extract configs".yellow()));
+    output.writeln(format!("   {}    # 只更新有变化的文件", "find -filter \"*.xml\" -extract work -update".yellow()));
+    output.writeln(format!("   {}    # 去掉开头的 compiled 目录", "find -filter \"compiled/*\" -extract out -strip-components 1".yellow()));
+    output.writeln(format!("   {}    # 按捕获组重命名", "find -match \"reanim/(.*)_head\" -extract-as \"heads/$1.reanim\"".yellow()));
     output.writeln("".to_string());
     output.writeln(format!("{}", "7. 输出重定向:".bright_white()));
     output.writeln(format!("   {}", "find -format \"$path,$size,$osize\" > files.csv".yellow()));
@@ -800,14 +927,14 @@ fn show_find_help(output: &mut OutputBuffer) {
     output.writeln(format!("{}", "注意:".bright_cyan()));
     output.writeln(format!("- 路径分隔符统一使用正斜杠 {} 进行搜索", "/".yellow()));
     output.writeln(format!("- 绝对路径以 {} 开头，相对路径基于当前目录", "/".yellow()));
-    output.writeln(format!("- 目录项的 {} 和 {} 显示为 {}", "$size".magenta(), "$osize".magenta(), "<DIR>".yellow()));
+    output.writeln(format!("- 目录项的 {}、{} 和 {} 显示为 {}", "$size".magenta(), "$osize".magenta(), "$time".magenta(), "<DIR>".yellow()));
     output.writeln(format!("- 所有输出都可以通过 {} 重定向到文件", "> filename".yellow()));
     output.writeln(format!("- 使用 {} 时将保持原有的相对路径结构", "-extract".yellow()));
     output.writeln(format!("- {} 模式下不输出任何内容，只提取文件", "-extract".yellow()));
 }
 
 /// 列出目录内容到缓冲区
-fn list_directory_to_buffer(fs: &PakFileSystem, target_path: &str, output: &mut OutputBuffer) {
+fn list_directory_to_buffer(fs: &PakFileSystem, target_path: &str, long_format: bool, output: &mut OutputBuffer) {
     let (directories, files) = fs.get_entries_at_path(target_path);
     
     let dirs_empty = directories.is_empty();
@@ -815,14 +942,27 @@ fn list_directory_to_buffer(fs: &PakFileSystem, target_path: &str, output: &mut
     
     // 先显示目录
     for dir in directories {
-        output.writeln(format!("{}", dir.cyan()));
+        if long_format {
+            output.writeln(format!("{:19}  {:>10}  {}", "", "<DIR>", dir.cyan()));
+        } else {
+            output.writeln(format!("{}", dir.cyan()));
+        }
     }
     
     // 再显示文件
     for file in files {
         let file_name_unix = file.file_name.replace('\\', "/");
-        let file_name = file_name_unix.split('/').last().unwrap_or(&file_name_unix);
-        output.writeln(format!("{}", file_name.bright_white()));
+        let file_name = file_name_unix.split('/').next_back().unwrap_or(&file_name_unix);
+        if long_format {
+            output.writeln(format!(
+                "{}  {:>10}  {}",
+                format_filetime(file._file_time),
+                file.z_size,
+                file_name.bright_white()
+            ));
+        } else {
+            output.writeln(format!("{}", file_name.bright_white()));
+        }
     }
     
     if dirs_empty && files_empty {
@@ -835,7 +975,7 @@ fn list_directory_to_buffer(fs: &PakFileSystem, target_path: &str, output: &mut
 
 
 /// 列出指定路径下的所有文件（包括子目录）到缓冲区（带格式化）
-fn find_all_files_in_path_to_buffer_with_format(fs: &PakFileSystem, base_path: &str, format_str: Option<&str>, output: &mut OutputBuffer) {
+fn find_all_files_in_path_to_buffer_with_format<R: Read + Seek>(fs: &PakFileSystem, base_path: &str, format_str: Option<&str>, hasher: &mut FormatHasher<'_, R>, output: &mut OutputBuffer) {
     let resolved_path = fs.resolve_path(base_path);
     let prefix = if resolved_path == "/" {
         ""
@@ -864,7 +1004,8 @@ fn find_all_files_in_path_to_buffer_with_format(fs: &PakFileSystem, base_path: &
     }
     
     for file in found_files {
-        let formatted = format_file_info(file, format_str);
+        let format = hasher.expand(file, format_str);
+        let formatted = format_file_info(file, format.as_deref());
         output.writeln(formatted);
     }
 }
@@ -874,7 +1015,7 @@ fn find_all_files_in_path_to_buffer_with_format(fs: &PakFileSystem, base_path: &
 
 
 /// 根据文件名查找文件和目录（限制在当前路径下）到缓冲区（带格式化）
-fn find_by_name_to_buffer_with_format(fs: &PakFileSystem, filename: &str, format_str: Option<&str>, output: &mut OutputBuffer) {
+fn find_by_name_to_buffer_with_format<R: Read + Seek>(fs: &PakFileSystem, filename: &str, format_str: Option<&str>, hasher: &mut FormatHasher<'_, R>, output: &mut OutputBuffer) {
     let current_prefix = if fs.current_path == "/" {
         ""
     } else {
@@ -908,7 +1049,7 @@ fn find_by_name_to_buffer_with_format(fs: &PakFileSystem, filename: &str, format
                 remaining.strip_prefix('\\').unwrap_or(remaining)
             };
             
-            let file_basename = relative_path.split('\\').last().unwrap_or(relative_path);
+            let file_basename = relative_path.split('\\').next_back().unwrap_or(relative_path);
             if file_basename == filename {
                 found_files.push(file);
             }
@@ -940,7 +1081,8 @@ fn find_by_name_to_buffer_with_format(fs: &PakFileSystem, filename: &str, format
     
     // 再显示文件
     for file in found_files {
-        let formatted = format_file_info(file, format_str);
+        let format = hasher.expand(file, format_str);
+        let formatted = format_file_info(file, format.as_deref());
         output.writeln(formatted);
     }
 }
@@ -950,13 +1092,13 @@ fn find_by_name_to_buffer_with_format(fs: &PakFileSystem, filename: &str, format
 
 
 /// 根据通配符模式查找文件到缓冲区（带格式化）
-fn find_by_pattern_to_buffer_with_format(fs: &PakFileSystem, pattern: &str, format_str: Option<&str>, output: &mut OutputBuffer) {
+fn find_by_pattern_to_buffer_with_format<R: Read + Seek>(fs: &PakFileSystem, pattern: &str, format_str: Option<&str>, hasher: &mut FormatHasher<'_, R>, output: &mut OutputBuffer) {
     let mut found = Vec::new();
     
     // 如果模式以/开头，从根目录搜索；否则基于当前路径搜索
-    let search_pattern = if pattern.starts_with('/') {
+    let search_pattern = if let Some(stripped) = pattern.strip_prefix('/') {
         // 移除开头的/，因为PAK文件路径不以/开头
-        pattern[1..].to_string()
+        stripped.to_string()
     } else {
         // 相对路径，添加当前路径前缀
         if fs.current_path == "/" {
@@ -976,13 +1118,14 @@ fn find_by_pattern_to_buffer_with_format(fs: &PakFileSystem, pattern: &str, form
     }
     
     for file in found {
-        let formatted = format_file_info(file, format_str);
+        let format = hasher.expand(file, format_str);
+        let formatted = format_file_info(file, format.as_deref());
         output.writeln(formatted);
     }
 }
 
 /// 根据正则表达式查找文件和目录到缓冲区（带格式化）
-fn find_by_regex_to_buffer_with_format(fs: &PakFileSystem, regex_pattern: &str, format_str: Option<&str>, output: &mut OutputBuffer) {
+fn find_by_regex_to_buffer_with_format<R: Read + Seek>(fs: &PakFileSystem, regex_pattern: &str, format_str: Option<&str>, hasher: &mut FormatHasher<'_, R>, output: &mut OutputBuffer) {
     // 编译正则表达式
     let regex = match Regex::new(regex_pattern) {
         Ok(r) => r,
@@ -1026,15 +1169,18 @@ fn find_by_regex_to_buffer_with_format(fs: &PakFileSystem, regex_pattern: &str,
         let unix_path = file.file_name.replace('\\', "/");
         
         if let Some(captures) = regex.captures(&unix_path) {
-            let formatted = format_file_info_with_captures(file, format_str, Some(&captures));
+            let format = hasher.expand(file, format_str);
+            let formatted = format_file_info_with_captures(file, format.as_deref(), Some(&captures));
             output.writeln(formatted);
         }
     }
 }
 
 /// 显示PAK文件信息到缓冲区
-fn show_pak_info_to_buffer(data: &[u8], _encrypted: bool, files: &[FileInfo], output: &mut OutputBuffer) {
-    output.writeln(format!("{}: {}", "PAK 文件大小".bright_cyan(), format!("{:.2} MB", data.len() as f64 / 1024.0 / 1024.0).bright_white()));
+fn show_pak_info_to_buffer(file_size: u64, variant: PakVariant, encoding: NameEncoding, files: &[FileInfo], output: &mut OutputBuffer) {
+    output.writeln(format!("{}: {}", "PAK 文件大小".bright_cyan(), format!("{:.2} MB", file_size as f64 / 1024.0 / 1024.0).bright_white()));
+    output.writeln(format!("{}: {}", "格式".bright_cyan(), variant.to_string().bright_white()));
+    output.writeln(format!("{}: {}", "文件名编码".bright_cyan(), encoding.to_string().bright_white()));
     output.writeln(format!("{}: {}", "文件数量".bright_cyan(), format!("{}", files.len()).bright_white()));
     
     let total_compressed: u32 = files.iter().map(|f| f.z_size).sum();
@@ -1047,152 +1193,79 @@ fn show_pak_info_to_buffer(data: &[u8], _encrypted: bool, files: &[FileInfo], ou
         let ratio = (total_compressed as f64 / total_uncompressed as f64) * 100.0;
         output.writeln(format!("{}: {}", "压缩率".bright_cyan(), format!("{:.1}%", ratio).bright_green()));
     }
-}
-
-/// 检查路径是否匹配通配符模式
-fn matches_glob_pattern(path: &str, pattern: &str) -> bool {
-    // 直接匹配，不进行路径分隔符转换，因为现在pattern已经是反斜杠格式
-    glob_match(path, pattern)
-}
-
-/// 实现基本的glob匹配
-fn glob_match(text: &str, pattern: &str) -> bool {
-    let text_chars: Vec<char> = text.chars().collect();
-    let pattern_chars: Vec<char> = pattern.chars().collect();
     
-    glob_match_recursive(&text_chars, &pattern_chars, 0, 0)
+    // 条目时间范围
+    let earliest = files.iter().map(|f| f._file_time).min();
+    let latest = files.iter().map(|f| f._file_time).max();
+    if let (Some(earliest), Some(latest)) = (earliest, latest) {
+        output.writeln(format!("{}: {}", "最早修改时间".bright_cyan(), format_filetime(earliest).bright_white()));
+        output.writeln(format!("{}: {}", "最晚修改时间".bright_cyan(), format_filetime(latest).bright_white()));
+    }
 }
 
-fn glob_match_recursive(text: &[char], pattern: &[char], t_idx: usize, p_idx: usize) -> bool {
-    // 模式结束
-    if p_idx >= pattern.len() {
-        return t_idx >= text.len();
-    }
-    
-    // 文本结束但模式未结束
-    if t_idx >= text.len() {
-        // 检查剩余模式是否都是*
-        return pattern[p_idx..].iter().all(|&c| c == '*');
-    }
-    
-    match pattern[p_idx] {
-        '*' => {
-            // *匹配0个或多个字符
-            // 尝试匹配0个字符
-            if glob_match_recursive(text, pattern, t_idx, p_idx + 1) {
-                return true;
-            }
-            // 尝试匹配1个或多个字符
-            for i in t_idx..text.len() {
-                if glob_match_recursive(text, pattern, i + 1, p_idx + 1) {
-                    return true;
-                }
-            }
-            false
-        }
-        '?' => {
-            // ?匹配单个字符
-            glob_match_recursive(text, pattern, t_idx + 1, p_idx + 1)
-        }
-        '[' => {
-            // 字符类匹配
-            if let Some(end_bracket) = pattern[p_idx..].iter().position(|&c| c == ']') {
-                let char_class = &pattern[p_idx + 1..p_idx + end_bracket];
-                let current_char = text[t_idx];
-                
-                if matches_char_class(current_char, char_class) {
-                    glob_match_recursive(text, pattern, t_idx + 1, p_idx + end_bracket + 1)
-                } else {
-                    false
-                }
-            } else {
-                // 没有找到闭合的]，按字面量匹配
-                text[t_idx] == pattern[p_idx] && 
-                glob_match_recursive(text, pattern, t_idx + 1, p_idx + 1)
-            }
-        }
-        c => {
-            // 字面量字符匹配
-            text[t_idx] == c && glob_match_recursive(text, pattern, t_idx + 1, p_idx + 1)
-        }
-    }
+/// find 的提取选项
+struct ExtractOptions<'a> {
+    /// 目标目录
+    dir: &'a str,
+    /// 输出路径模板（-extract-as），None 时使用条目路径
+    template: Option<&'a str>,
+    /// 条目在目标目录中的布局
+    layout: PathLayout,
+    /// 输出文件已存在时的处理方式
+    policy: OverwritePolicy,
+    /// 模板中 $hash 使用的校验算法
+    hash_algorithm: HashAlgorithm,
 }
 
-fn matches_char_class(ch: char, char_class: &[char]) -> bool {
-    if char_class.is_empty() {
-        return false;
-    }
-    
-    let negated = char_class[0] == '!';
-    let chars_to_check = if negated { &char_class[1..] } else { char_class };
-    
-    let mut i = 0;
-    let mut matched = false;
-    
-    while i < chars_to_check.len() {
-        if i + 2 < chars_to_check.len() && chars_to_check[i + 1] == '-' {
-            // 范围匹配如 a-z
-            let start = chars_to_check[i];
-            let end = chars_to_check[i + 2];
-            if ch >= start && ch <= end {
-                matched = true;
-                break;
-            }
-            i += 3;
-        } else {
-            // 单个字符匹配
-            if ch == chars_to_check[i] {
-                matched = true;
-                break;
-            }
-            i += 1;
-        }
-    }
-    
-    if negated { !matched } else { matched }
+/// find -format 中 $hash 变量的计算：只有格式字符串用到时才读取条目内容
+struct FormatHasher<'a, R> {
+    reader: &'a mut PakReader<R>,
+    algorithm: HashAlgorithm,
 }
 
-/// 检测PAK文件是否加密
-fn detect_encryption(data: &[u8]) -> bool {
-    if data.len() < 8 {
-        return false;
+impl<R: Read + Seek> FormatHasher<'_, R> {
+    /// 把格式字符串中的 $hash 替换为条目内容的校验和（读取失败时为错误信息）
+    fn expand(&mut self, file: &FileInfo, format_str: Option<&str>) -> Option<String> {
+        let format = format_str?;
+        if !format.contains("$hash") {
+            return Some(format.to_string());
+        }
+        let hash = match self.reader.find_entry(&file.file_name) {
+            Some(index) => hash_entry(self.reader, index, self.algorithm).unwrap_or_else(|e| format!("<{}>", e)),
+            None => "<?>".to_string(),
+        };
+        Some(format.replace("$hash", &hash))
     }
-    
-    // 检查magic number（PC版本应该是0xBAC04AC0）
-    let magic = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
-    magic != 0xBAC04AC0
 }
 
 /// 提取筛选的文件到指定目录
-fn extract_filtered_files(
+fn extract_filtered_files<R: Read + Seek>(
     fs: &PakFileSystem, 
-    pak_data: &[u8], 
-    extract_dir: &str,
+    reader: &mut PakReader<R>, 
     search_type: Option<&str>,
-    search_value: Option<&str>
-) -> io::Result<usize> {
-    // 解析PAK信息来获取文件数据偏移
-    let (_pak_info, header_size) = parse_pak_info(pak_data)?;
-    
+    search_value: Option<&str>,
+    extract: &ExtractOptions,
+    output: &mut OutputBuffer
+) -> io::Result<WriteStats> {
     // 根据搜索条件筛选文件
     let filtered_files = match search_type {
         Some("name") => {
             if let Some(filename) = search_value {
-                filter_files_by_name(fs, filename)
+                filter_files_by_name(&fs.files, filename)
             } else {
                 Vec::new()
             }
         },
         Some("filter") => {
             if let Some(pattern) = search_value {
-                filter_files_by_pattern(fs, pattern)
+                filter_files_by_pattern(&fs.files, pattern)
             } else {
                 Vec::new()
             }
         },
         Some("match") => {
             if let Some(regex_pattern) = search_value {
-                filter_files_by_regex(fs, regex_pattern)?
+                filter_files_by_regex(&fs.files, regex_pattern)?
             } else {
                 Vec::new()
             }
@@ -1205,98 +1278,111 @@ fn extract_filtered_files(
     };
     
     if filtered_files.is_empty() {
-        return Ok(0);
+        return Ok(WriteStats::default());
     }
     
+    // 模板中的 $1、$2 等引用 -match 的捕获组（上面已经检查过正则表达式）
+    let capture_regex = match (search_type, search_value, extract.template) {
+        (Some("match"), Some(regex_pattern), Some(_)) => Regex::new(regex_pattern).ok(),
+        _ => None,
+    };
+    
     // 创建输出目录
-    fs::create_dir_all(extract_dir)?;
+    fs::create_dir_all(extract.dir)?;
     
-    // 提取文件
-    let mut file_offset = header_size;
-    let mut extracted_count = 0;
+    // 先确定所有输出路径，不覆盖时在写入任何文件之前检查
+    let mut targets = Vec::new();
+    let mut written_paths = HashSet::new();
     
-    for file_info in &fs.files {
-        // 检查这个文件是否在筛选列表中
-        let should_extract = filtered_files.iter().any(|f| f.file_name == file_info.file_name);
+    for index in filtered_files {
+        let file_info = &fs.files[index];
         
-        if should_extract {
-            // 检查数据边界
-            if file_offset + file_info.z_size as usize > pak_data.len() {
-                return Err(io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    format!("文件 {} 数据超出PAK文件边界", file_info.file_name)
-                ));
+        // 确定输出的相对路径：按模板生成，或使用条目路径
+        let target = match extract.template {
+            Some(template) => {
+                let unix_path = file_info.file_name.replace('\\', "/");
+                let captures = capture_regex.as_ref().and_then(|regex| regex.captures(&unix_path));
+                let template = if template.contains("$hash") {
+                    template.replace("$hash", &hash_entry(reader, index, extract.hash_algorithm)?)
+                } else {
+                    template.to_string()
+                };
+                format_file_info_with_captures(file_info, Some(&template), captures.as_ref())
             }
-            
-            // 读取文件数据
-            let file_data = &pak_data[file_offset..file_offset + file_info.z_size as usize];
-            
-            // 创建输出文件路径，保持相对路径
-            let output_file_path = Path::new(extract_dir).join(&file_info.file_name);
-            ensure_directory_exists(&output_file_path)?;
-            
-            // 写入文件
-            let mut output_file = File::create(&output_file_path)?;
-            output_file.write_all(file_data)?;
-            
-            extracted_count += 1;
+            None => file_info.file_name.clone(),
+        };
+        
+        // 清理输出路径，保证输出不会离开目标目录
+        let Some(safe_path) = sanitize_entry_path(&target) else {
+            output.writeln(format!("{}", format!("警告: 跳过不安全的输出路径: {}（{}）", target, file_info.file_name).yellow()));
+            continue;
+        };
+        if safe_path.is_rewritten() {
+            output.writeln(format!("{}", format!(
+                "警告: 输出路径已改写: {} -> {}（{}）",
+                target, safe_path.relative.display(), safe_path.reason_text()
+            ).yellow()));
         }
         
-        file_offset += file_info.z_size as usize;
-    }
-    
-    Ok(extracted_count)
-}
-
-/// 根据文件名筛选文件
-fn filter_files_by_name<'a>(fs: &'a PakFileSystem, filename: &str) -> Vec<&'a FileInfo> {
-    let mut result = Vec::new();
-    
-    for file in &fs.files {
-        let unix_path = file.file_name.replace('\\', "/");
-        let file_basename = unix_path.split('/').last().unwrap_or(&unix_path);
-        if file_basename == filename {
-            result.push(file);
+        // 按布局调整输出路径
+        let Some(relative) = extract.layout.apply(&safe_path.relative) else {
+            output.writeln(format!("{}", format!("警告: 跳过目录层级不足的条目: {}", file_info.file_name).yellow()));
+            continue;
+        };
+        let output_file_path = Path::new(extract.dir).join(relative);
+        if !written_paths.insert(output_file_path.clone()) {
+            output.writeln(format!("{}", format!(
+                "警告: 多个条目写入同一路径: {}（{}）", output_file_path.display(), file_info.file_name
+            ).yellow()));
         }
+        targets.push((index, output_file_path));
     }
     
-    result
-}
-
-/// 根据通配符模式筛选文件
-fn filter_files_by_pattern<'a>(fs: &'a PakFileSystem, pattern: &str) -> Vec<&'a FileInfo> {
-    let mut result = Vec::new();
-    
-    for file in &fs.files {
-        let unix_path = file.file_name.replace('\\', "/");
-        if matches_glob_pattern(&unix_path, pattern) {
-            result.push(file);
+    if extract.policy == OverwritePolicy::Refuse {
+        // -flatten 等布局下多个条目可能写入同一路径，每个路径只检查一次
+        let mut checked = HashSet::new();
+        let existing: Vec<_> = targets.iter()
+            .map(|(_, path)| path)
+            .filter(|path| checked.insert(*path) && fs::symlink_metadata(path).is_ok())
+            .collect();
+        if let Some(first) = existing.first() {
+            output.writeln(format!("{}", format!(
+                "{} 个文件已存在，没有提取任何文件；使用 -force、-skip-existing、-update 或 -backup 指定覆盖方式",
+                existing.len()
+            ).yellow()));
+            return Err(PakError::OutputExists { path: first.to_path_buf() }.into());
         }
     }
     
-    result
-}
-
-/// 根据正则表达式筛选文件
-fn filter_files_by_regex<'a>(fs: &'a PakFileSystem, regex_pattern: &str) -> io::Result<Vec<&'a FileInfo>> {
-    let regex = Regex::new(regex_pattern).map_err(|e| {
-        io::Error::new(io::ErrorKind::InvalidInput, format!("正则表达式错误: {}", e))
-    })?;
-    
-    let mut result = Vec::new();
+    // 上面已确认输出文件都不存在，之后出现的同名文件只能来自本次提取中写入同一路径的条目
+    // （重名、-flatten 或 -extract-as），与解包相同，后者覆盖前者
+    let file_policy = match extract.policy {
+        OverwritePolicy::Refuse => OverwritePolicy::Force,
+        policy => policy,
+    };
     
-    for file in &fs.files {
-        let unix_path = file.file_name.replace('\\', "/");
-        if regex.is_match(&unix_path) {
-            result.push(file);
+    // 提取文件（只读取被选中的条目）
+    let mut stats = WriteStats::default();
+    for (index, output_file_path) in targets {
+        let file_info = &fs.files[index];
+        
+        // 读取文件数据（压缩模式下解压）
+        let file_data = reader.read_entry(index)?;
+        ensure_directory_exists(&output_file_path)?;
+        
+        // 按覆盖策略写入文件，并还原PAK中记录的修改时间
+        let outcome = write_file_with_policy(&output_file_path, &file_data, file_info._file_time, file_policy)?;
+        if let WriteOutcome::BackedUp(backup_path) = &outcome {
+            output.writeln(format!("已备份: {} -> {}", output_file_path.display(), backup_path.display()));
         }
+        stats.record(&outcome);
     }
     
-    Ok(result)
+    Ok(stats)
 }
 
 /// 筛选当前路径下的所有文件
-fn filter_files_in_current_path<'a>(fs: &'a PakFileSystem) -> Vec<&'a FileInfo> {
+fn filter_files_in_current_path(fs: &PakFileSystem) -> Vec<usize> {
     let prefix = if fs.current_path == "/" {
         ""
     } else {
@@ -1305,23 +1391,100 @@ fn filter_files_in_current_path<'a>(fs: &'a PakFileSystem) -> Vec<&'a FileInfo>
     
     let mut result = Vec::new();
     
-    for file in &fs.files {
+    for (index, file) in fs.files.iter().enumerate() {
         let file_path = &file.file_name;
         
         if prefix.is_empty() {
             // 根目录，包含所有文件
-            result.push(file);
+            result.push(index);
         } else {
             // 检查文件是否在指定路径下
             let normalized_prefix = prefix.replace('/', "\\");
             if file_path.starts_with(&normalized_prefix) {
                 let remaining = &file_path[normalized_prefix.len()..];
                 if remaining.starts_with('\\') || remaining.is_empty() {
-                    result.push(file);
+                    result.push(index);
                 }
             }
         }
     }
     
     result
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::writer::PakWriter;
+
+    fn open_pak(entries: &[(&str, &[u8])]) -> PakReader<Cursor<Vec<u8>>> {
+        let mut writer = PakWriter::new();
+        for (name, data) in entries {
+            writer.add_bytes(name, data.to_vec()).unwrap();
+        }
+        let mut bytes = Vec::new();
+        writer.write_to(&mut bytes).unwrap();
+        PakReader::new(Cursor::new(bytes)).unwrap()
+    }
+
+    fn extract_all(reader: &mut PakReader<Cursor<Vec<u8>>>, dir: &Path, template: Option<&str>, layout: PathLayout) -> io::Result<WriteStats> {
+        let fs = PakFileSystem::new(reader.files().to_vec());
+        let dir = dir.to_str().unwrap();
+        let extract = ExtractOptions {
+            dir,
+            template,
+            layout,
+            policy: OverwritePolicy::Refuse,
+            hash_algorithm: HashAlgorithm::default(),
+        };
+        extract_filtered_files(&fs, reader, None, None, &extract, &mut OutputBuffer::new())
+    }
+
+    #[test]
+    fn refuse_extract_lets_later_entry_win_on_in_run_collision() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut reader = open_pak(&[("a\\same.txt", b"first"), ("b\\same.txt", b"second"), ("b\\other.txt", b"x")]);
+
+        let stats = extract_all(&mut reader, temp.path(), Some("out/$name"), PathLayout::Full).unwrap();
+
+        assert_eq!(stats.created, 2);
+        assert_eq!(stats.overwritten, 1);
+        assert_eq!(fs::read(temp.path().join("out/same.txt")).unwrap(), b"second");
+        assert_eq!(fs::read(temp.path().join("out/other.txt")).unwrap(), b"x");
+    }
+
+    #[test]
+    fn refuse_extract_flattens_entries_from_several_directories() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut reader = open_pak(&[("a\\x\\same.txt", b"1"), ("b\\x\\same.txt", b"2"), ("c\\x\\same.txt", b"3")]);
+
+        let stats = extract_all(&mut reader, temp.path(), None, PathLayout::Flatten).unwrap();
+
+        assert_eq!((stats.created, stats.overwritten), (1, 2));
+        assert_eq!(fs::read(temp.path().join("same.txt")).unwrap(), b"3");
+    }
+
+    #[test]
+    fn refuse_extract_strips_components_into_one_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut reader = open_pak(&[("a\\x\\same.txt", b"1"), ("b\\x\\same.txt", b"2")]);
+
+        let stats = extract_all(&mut reader, temp.path(), None, PathLayout::StripComponents(1)).unwrap();
+
+        assert_eq!((stats.created, stats.overwritten), (1, 1));
+        assert_eq!(fs::read(temp.path().join("x/same.txt")).unwrap(), b"2");
+    }
+
+    #[test]
+    fn refuse_extract_writes_nothing_when_any_output_exists() {
+        let temp = tempfile::tempdir().unwrap();
+        fs::write(temp.path().join("b.txt"), b"keep").unwrap();
+        let mut reader = open_pak(&[("a.txt", b"a"), ("b.txt", b"b")]);
+
+        let error = extract_all(&mut reader, temp.path(), None, PathLayout::Full).unwrap_err();
+
+        assert!(matches!(PakError::from_io(&error), Some(PakError::OutputExists { .. })));
+        assert!(!temp.path().join("a.txt").exists());
+        assert_eq!(fs::read(temp.path().join("b.txt")).unwrap(), b"keep");
+    }
+}
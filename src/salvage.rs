@@ -0,0 +1,330 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use flate2::read::ZlibDecoder;
+use crate::encoding::NameEncoding;
+use crate::entry_path::sanitize_entry_path;
+use crate::error::PakError;
+use crate::overwrite::{write_file_with_policy, OverwritePolicy, WriteOutcome};
+use crate::probe::PakVariant;
+use crate::scan::{scan_pak, IndexProblem, IndexRecord, PakScan};
+use crate::utils::{ensure_directory_exists, is_directory_empty};
+
+/// 救援报告的默认文件名（位于输出目录中，打包时不作为条目）
+pub const SALVAGE_REPORT_FILE_NAME: &str = ".paksalvage";
+
+/// 不完整或内容无法确认的条目写出时附加的扩展名，避免被当作完好的文件使用
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// 救援选项
+#[derive(Debug, Clone, Default)]
+pub struct SalvageOptions {
+    /// 文件名编码（Auto 时自动识别）
+    pub encoding: NameEncoding,
+    /// 输出文件已存在时的处理方式（Refuse 时要求输出目录为空）
+    pub overwrite: OverwritePolicy,
+    /// 报告路径，None 表示输出目录下的 .paksalvage
+    pub report: Option<PathBuf>,
+}
+
+/// 条目的救援结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SalvageStatus {
+    /// 数据完整，已按原名写出
+    Recovered,
+    /// 得到了完整长度的数据，但数据位置是推算的，内容无法校验，已写出为 .partial 文件
+    Uncertain,
+    /// 只得到部分数据，已写出为 .partial 文件
+    Partial,
+    /// 没有得到任何数据
+    Lost,
+}
+
+impl fmt::Display for SalvageStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SalvageStatus::Recovered => "恢复",
+            SalvageStatus::Uncertain => "存疑",
+            SalvageStatus::Partial => "部分",
+            SalvageStatus::Lost => "丢失",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 一个条目的救援结果
+#[derive(Debug, Clone)]
+pub struct SalvageEntry {
+    /// 条目名（空文件名显示为序号）
+    pub name: String,
+    pub status: SalvageStatus,
+    /// 得到的字节数（压缩模式下为解压后的大小）
+    pub recovered_size: u64,
+    /// 索引中记录的大小（压缩模式下为原始大小）
+    pub expected_size: u64,
+    /// 没有完整恢复的原因
+    pub reason: Option<String>,
+}
+
+/// 救援结果
+#[derive(Debug, Clone)]
+pub struct SalvageReport {
+    /// 使用的格式变体
+    pub variant: PakVariant,
+    /// 索引中能读出的每个条目
+    pub entries: Vec<SalvageEntry>,
+    /// 无法解析的索引区域（其中的条目名和大小都已丢失）
+    pub damaged_regions: Vec<String>,
+}
+
+impl SalvageReport {
+    /// 某种结果的条目数量
+    pub fn count(&self, status: SalvageStatus) -> usize {
+        self.entries.iter().filter(|entry| entry.status == status).count()
+    }
+
+    /// 写出报告：每行一个条目，字段用制表符分隔（结果、条目名、得到/应有字节数、原因）
+    pub fn write_to(&self, writer: &mut impl Write, pak_path: &Path) -> io::Result<()> {
+        writeln!(writer, "# pkt salvage: {}", pak_path.display())?;
+        writeln!(writer, "# 格式: {}", self.variant)?;
+        writeln!(
+            writer,
+            "# 恢复 {}，存疑 {}，部分 {}，丢失 {}",
+            self.count(SalvageStatus::Recovered), self.count(SalvageStatus::Uncertain),
+            self.count(SalvageStatus::Partial), self.count(SalvageStatus::Lost)
+        )?;
+        for region in &self.damaged_regions {
+            writeln!(writer, "# 索引损坏: {}", region)?;
+        }
+        for entry in &self.entries {
+            write!(writer, "{}\t{}\t{}/{}", entry.status, entry.name, entry.recovered_size, entry.expected_size)?;
+            if let Some(reason) = &entry.reason {
+                write!(writer, "\t{}", reason)?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// 从截断或损坏的PAK中尽量提取条目
+///
+/// 索引中无法解析的记录会被跳过，从下一条看起来合理的记录继续；数据完整的条目按原名写出，
+/// 只有部分数据的条目写为 `.partial` 文件，最后写出逐条目的报告。
+pub fn salvage_pak(pak_path: &Path, output_dir: &Path, options: &SalvageOptions) -> io::Result<SalvageReport> {
+    if !pak_path.exists() {
+        return Err(PakError::InputNotFound { path: pak_path.to_path_buf() }.into());
+    }
+    if options.overwrite == OverwritePolicy::Refuse && output_dir.exists() && !is_directory_empty(output_dir)? {
+        return Err(PakError::OutputNotEmpty { path: output_dir.to_path_buf() }.into());
+    }
+
+    let mut reader = BufReader::new(File::open(pak_path)?);
+    let scan = scan_pak(&mut reader)?;
+    fs::create_dir_all(output_dir)?;
+
+    println!("正在救援: {}", pak_path.display());
+    println!("输出目录: {}", output_dir.display());
+    println!("格式: {}", scan.variant);
+    println!();
+
+    let file_policy = match options.overwrite {
+        OverwritePolicy::Refuse => OverwritePolicy::Force,
+        policy => policy,
+    };
+    let encoding = options.encoding.detect(scan.index.records.iter().map(|record| record.raw_name.as_slice()));
+
+    let mut entries = Vec::with_capacity(scan.index.records.len());
+    for (index, (record, offset)) in scan.index.records.iter().zip(scan.data_offsets()).enumerate() {
+        let name = encoding.decode(&record.raw_name);
+        let name = if name.is_empty() { format!("#{}", index) } else { name };
+        let (mut entry, data) = salvage_entry(&mut reader, &scan, record, offset, name)?;
+
+        if let Some(data) = data {
+            match sanitize_entry_path(&entry.name) {
+                Some(safe_path) => {
+                    let mut output_path = output_dir.join(&safe_path.relative);
+                    if matches!(entry.status, SalvageStatus::Partial | SalvageStatus::Uncertain) {
+                        let mut partial = output_path.into_os_string();
+                        partial.push(PARTIAL_SUFFIX);
+                        output_path = PathBuf::from(partial);
+                    }
+                    ensure_directory_exists(&output_path)?;
+                    let outcome = write_file_with_policy(&output_path, &data, record.file_time, file_policy)?;
+                    if let WriteOutcome::BackedUp(backup_path) = &outcome {
+                        println!("已备份: {} -> {}", output_path.display(), backup_path.display());
+                    }
+                }
+                None => {
+                    entry.status = SalvageStatus::Lost;
+                    entry.recovered_size = 0;
+                    entry.reason = Some("条目路径不安全".to_string());
+                }
+            }
+        }
+
+        if entry.status != SalvageStatus::Recovered {
+            println!("{}: {}（{}）", entry.status, entry.name, entry.reason.as_deref().unwrap_or_default());
+        }
+        entries.push(entry);
+    }
+
+    let report = SalvageReport {
+        variant: scan.variant,
+        entries,
+        damaged_regions: damaged_regions(&scan),
+    };
+    for region in &report.damaged_regions {
+        println!("索引损坏: {}", region);
+    }
+
+    let report_path = options.report.clone().unwrap_or_else(|| output_dir.join(SALVAGE_REPORT_FILE_NAME));
+    let mut report_file = io::BufWriter::new(File::create(&report_path)?);
+    report.write_to(&mut report_file, pak_path)?;
+    report_file.flush()?;
+
+    println!();
+    println!(
+        "救援完成！恢复 {}，存疑 {}，部分 {}，丢失 {} 个条目",
+        report.count(SalvageStatus::Recovered), report.count(SalvageStatus::Uncertain),
+        report.count(SalvageStatus::Partial), report.count(SalvageStatus::Lost)
+    );
+    println!("报告: {}", report_path.display());
+    Ok(report)
+}
+
+/// 读取一个条目能得到的数据，返回结果和需要写出的数据
+fn salvage_entry<R: Read + Seek>(
+    reader: &mut R,
+    scan: &PakScan,
+    record: &IndexRecord,
+    offset: Option<u64>,
+    name: String,
+) -> io::Result<(SalvageEntry, Option<Vec<u8>>)> {
+    let expected_size = if scan.variant.compressed { record.size } else { record.z_size } as u64;
+    let mut entry = SalvageEntry {
+        name,
+        status: SalvageStatus::Lost,
+        recovered_size: 0,
+        expected_size,
+        reason: None,
+    };
+
+    let Some(offset) = offset else {
+        entry.reason = Some("之前的索引记录损坏，无法确定数据位置".to_string());
+        return Ok((entry, None));
+    };
+    let end = offset + record.z_size as u64;
+    let available = end.min(scan.file_len).saturating_sub(offset);
+    if available == 0 && record.z_size > 0 {
+        entry.reason = Some("数据在文件末尾之后".to_string());
+        return Ok((entry, None));
+    }
+
+    let mut raw = vec![0u8; available as usize];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut raw)?;
+    let key = scan.variant.key();
+    raw.iter_mut().for_each(|byte| *byte ^= key);
+    let truncated = end > scan.file_len;
+
+    let data = if scan.variant.compressed {
+        // 解压出错时保留已经解压出的数据
+        let mut data = Vec::with_capacity(record.size as usize);
+        let result = ZlibDecoder::new(raw.as_slice()).read_to_end(&mut data);
+        if truncated {
+            entry.reason = Some(format!("数据在文件末尾被截断（{}/{} 字节）", available, record.z_size));
+        } else if let Err(e) = result {
+            entry.reason = Some(format!("解压失败: {}", e));
+        } else if data.len() as u64 != expected_size {
+            entry.reason = Some(format!("解压后大小为 {} 字节，索引记录为 {} 字节", data.len(), expected_size));
+        }
+        data
+    } else {
+        if truncated {
+            entry.reason = Some("数据在文件末尾被截断".to_string());
+        }
+        raw
+    };
+
+    // 压缩条目有 zlib 校验和，解压成功就说明位置正确；未压缩的条目无法确认推算出的位置
+    if entry.reason.is_none() && !scan.variant.compressed && scan.is_offset_inferred(record) {
+        entry.recovered_size = data.len() as u64;
+        entry.status = SalvageStatus::Uncertain;
+        entry.reason = Some("数据位置从文件末尾推算，文件被截断时内容是错误的".to_string());
+        return Ok((entry, Some(data)));
+    }
+
+    entry.recovered_size = data.len() as u64;
+    entry.status = match entry.reason {
+        None => SalvageStatus::Recovered,
+        Some(_) if data.is_empty() => return Ok((entry, None)),
+        Some(_) => SalvageStatus::Partial,
+    };
+    Ok((entry, Some(data)))
+}
+
+/// 描述索引中无法解析的区域
+fn damaged_regions(scan: &PakScan) -> Vec<String> {
+    scan.index.problems.iter().filter_map(|problem| match *problem {
+        IndexProblem::DamagedFlag { .. } => None,
+        IndexProblem::BadRecord { offset, resumed_at: Some(next), .. } => {
+            Some(format!("0x{:X}..0x{:X} 无法解析，其中的条目已丢失", offset, next))
+        }
+        IndexProblem::BadRecord { offset, resumed_at: None, .. } => {
+            Some(format!("0x{:X} 之后无法解析，其余条目已丢失", offset))
+        }
+        IndexProblem::Truncated { offset } => Some(format!("索引在 0x{:X} 处被截断，其余条目已丢失", offset)),
+        IndexProblem::TooLarge { limit } => Some(format!("索引超过 {} 字节的上限，之后的条目没有扫描", limit)),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::PakWriter;
+
+    /// 写出一个三个条目的PAK，破坏第二条索引记录的文件名，并截掉末尾 `truncate` 字节
+    fn write_damaged_pak(path: &Path, compress: Option<u32>, truncate: usize) {
+        let mut writer = PakWriter::new().encrypted(false).compression(compress);
+        writer.add_bytes("a.txt", b"aaaa".to_vec()).unwrap();
+        writer.add_bytes("b.txt", b"bbbb".to_vec()).unwrap();
+        writer.add_bytes("c.txt", b"cccccc".to_vec()).unwrap();
+        let mut bytes = Vec::new();
+        writer.write_to(&mut bytes).unwrap();
+
+        let record_len = 2 + "a.txt".len() + if compress.is_some() { 16 } else { 12 };
+        bytes[8 + record_len + 2] = 0x01;
+        bytes.truncate(bytes.len() - truncate);
+        fs::write(path, bytes).unwrap();
+    }
+
+    fn salvage(compress: Option<u32>, truncate: usize) -> (tempfile::TempDir, SalvageReport) {
+        let temp = tempfile::tempdir().unwrap();
+        let pak_path = temp.path().join("damaged.pak");
+        write_damaged_pak(&pak_path, compress, truncate);
+        let report = salvage_pak(&pak_path, &temp.path().join("out"), &SalvageOptions::default()).unwrap();
+        (temp, report)
+    }
+
+    #[test]
+    fn stored_entry_located_from_the_end_of_a_truncated_file_is_uncertain() {
+        let (temp, report) = salvage(None, 2);
+
+        let statuses: Vec<_> = report.entries.iter().map(|entry| (entry.name.as_str(), entry.status)).collect();
+        assert_eq!(statuses, [("a.txt", SalvageStatus::Recovered), ("c.txt", SalvageStatus::Uncertain)]);
+        assert!(!temp.path().join("out/c.txt").exists());
+        assert!(temp.path().join("out/c.txt.partial").exists());
+        assert_eq!(fs::read(temp.path().join("out/a.txt")).unwrap(), b"aaaa");
+    }
+
+    #[test]
+    fn compressed_entry_located_from_the_end_is_checked_by_zlib() {
+        let (temp, report) = salvage(Some(6), 0);
+
+        assert_eq!(report.entries[1].name, "c.txt");
+        assert_eq!(report.entries[1].status, SalvageStatus::Recovered);
+        assert_eq!(fs::read(temp.path().join("out/c.txt")).unwrap(), b"cccccc");
+    }
+}
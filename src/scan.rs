@@ -0,0 +1,444 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use crate::error::{PakError, PakResult};
+use crate::pak::PakInfo;
+use crate::probe::{magic_key, probe, PakVariant};
+
+/// 读取索引时的初始缓冲区大小
+const SCAN_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 扫描到的一条索引记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexRecord {
+    /// 记录的标志字节在文件中的偏移
+    pub offset: u64,
+    /// 所在的连续段（每跳过一段无法解析的索引加一）
+    pub segment: usize,
+    /// 原始文件名字节
+    pub raw_name: Vec<u8>,
+    /// 数据区中的大小
+    pub z_size: u32,
+    /// 原始大小（仅压缩模式）
+    pub size: u32,
+    /// FILETIME 时间戳
+    pub file_time: u64,
+}
+
+/// 扫描索引时发现的结构问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexProblem {
+    /// 条目标志无效，但记录的其余部分完整，已按 0x00 读取
+    DamagedFlag { offset: u64, flag: u8 },
+    /// 记录无效（标志不是 0x00 或 0x80，或者文件名、大小字段损坏）；
+    /// `resumed_at` 为重新找到的下一条合理记录的偏移
+    BadRecord { offset: u64, flag: u8, resumed_at: Option<u64> },
+    /// 索引在读完之前到达了数据末尾
+    Truncated { offset: u64 },
+    /// 索引超过了 `PakInfo::MAX_INDEX_SIZE`，之后的部分没有扫描
+    TooLarge { limit: u64 },
+}
+
+/// 容错扫描索引的结果
+#[derive(Debug, Clone, Default)]
+pub struct IndexScan {
+    /// 所有能读出的记录
+    pub records: Vec<IndexRecord>,
+    /// 发现的结构问题
+    pub problems: Vec<IndexProblem>,
+    /// 索引结束标志之后的偏移（数据区起点），没有找到结束标志时为 None
+    pub header_size: Option<u64>,
+}
+
+/// 容错地扫描索引（`data` 为解密后的文件开头，包含 magic 和版本）
+///
+/// 遇到无效记录时不立即停止：只有标志字节损坏的记录按 0x00 读取，否则向后寻找下一条
+/// 看起来合理的记录（见 [`is_plausible_record`]）继续扫描，因此一次扫描可以报告多处损坏。
+pub fn scan_index(data: &[u8], compressed: bool) -> IndexScan {
+    let record_tail = record_tail_len(compressed);
+    let mut scan = IndexScan::default();
+    let mut segment = 0;
+    let mut pos = 8;
+
+    loop {
+        let Some(&flag) = data.get(pos) else {
+            scan.problems.push(IndexProblem::Truncated { offset: pos as u64 });
+            return scan;
+        };
+
+        if flag == PakInfo::INFO_END {
+            scan.header_size = Some(pos as u64 + 1);
+            return scan;
+        }
+
+        let Some(&name_len) = data.get(pos + 1) else {
+            scan.problems.push(IndexProblem::Truncated { offset: pos as u64 });
+            return scan;
+        };
+        let name_start = pos + 2;
+        let tail_start = name_start + name_len as usize;
+        if flag == 0 && tail_start + record_tail > data.len() {
+            scan.problems.push(IndexProblem::Truncated { offset: pos as u64 });
+            return scan;
+        }
+
+        let valid = record_end(data, pos, compressed).filter(|&end| next_looks_valid(data, end, compressed));
+        if valid.is_none() {
+            let resumed_at = (pos + 1..data.len()).find(|&next| is_plausible_record(data, next, compressed));
+            scan.problems.push(IndexProblem::BadRecord {
+                offset: pos as u64,
+                flag,
+                resumed_at: resumed_at.map(|next| next as u64),
+            });
+            match resumed_at {
+                Some(next) => {
+                    pos = next;
+                    segment += 1;
+                    continue;
+                }
+                None => return scan,
+            }
+        }
+        if flag != 0 {
+            scan.problems.push(IndexProblem::DamagedFlag { offset: pos as u64, flag });
+        }
+
+        let tail = &data[tail_start..tail_start + record_tail];
+        let z_size = u32::from_le_bytes([tail[0], tail[1], tail[2], tail[3]]);
+        let (size, time_start) = if compressed {
+            (u32::from_le_bytes([tail[4], tail[5], tail[6], tail[7]]), 8)
+        } else {
+            (z_size, 4)
+        };
+        let mut time_bytes = [0u8; 8];
+        time_bytes.copy_from_slice(&tail[time_start..time_start + 8]);
+
+        scan.records.push(IndexRecord {
+            offset: pos as u64,
+            segment,
+            raw_name: data[name_start..tail_start].to_vec(),
+            z_size,
+            size,
+            file_time: u64::from_le_bytes(time_bytes),
+        });
+        pos = tail_start + record_tail;
+    }
+}
+
+/// `pos` 处是否像一条索引记录：标志 0x00、非空且不含控制字符的文件名、
+/// 记录完整，并且紧跟着下一条记录的标志或索引结束标志
+pub fn is_plausible_record(data: &[u8], pos: usize, compressed: bool) -> bool {
+    data.get(pos) == Some(&0)
+        && data.get(pos + 1).is_some_and(|&name_len| name_len > 0)
+        && record_end(data, pos, compressed).is_some_and(|end| matches!(data.get(end).copied(), Some(0 | PakInfo::INFO_END)))
+}
+
+/// 不看标志字节时 `pos` 处记录的结束位置：文件名不含控制字符，并且记录完整
+fn record_end(data: &[u8], pos: usize, compressed: bool) -> Option<usize> {
+    let name_len = *data.get(pos + 1)? as usize;
+    let name_start = pos + 2;
+    let end = name_start + name_len + record_tail_len(compressed);
+    if end > data.len() {
+        return None;
+    }
+    let name = &data[name_start..name_start + name_len];
+    name.iter().all(|&byte| byte >= 0x20 && byte != 0x7F).then_some(end)
+}
+
+/// 一条记录之后的位置是否合理：下一条记录的标志、索引结束标志、只有标志损坏的下一条记录，
+/// 或者已经到达缓冲区末尾（由截断检查处理）
+fn next_looks_valid(data: &[u8], next: usize, compressed: bool) -> bool {
+    match data.get(next).copied() {
+        None | Some(0 | PakInfo::INFO_END) => true,
+        Some(_) => record_end(data, next, compressed)
+            .is_some_and(|after| matches!(data.get(after).copied(), None | Some(0 | PakInfo::INFO_END))),
+    }
+}
+
+/// 读取并解密文件开头，直到能扫描完整个索引（或到达文件末尾、索引长度上限）
+///
+/// 索引损坏时找不到结束标志，最多读取 `PakInfo::MAX_INDEX_SIZE` 字节，不会把整个文件读入内存。
+fn read_index_region<R: Read + Seek>(reader: &mut R, key: u8, compressed: bool) -> io::Result<(Vec<u8>, IndexScan)> {
+    read_index_region_with_limit(reader, key, compressed, PakInfo::MAX_INDEX_SIZE as u64)
+}
+
+fn read_index_region_with_limit<R: Read + Seek>(reader: &mut R, key: u8, compressed: bool, max_size: u64) -> io::Result<(Vec<u8>, IndexScan)> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let limit = file_len.min(max_size);
+
+    let mut data = Vec::new();
+    let mut chunk_size = SCAN_CHUNK_SIZE;
+    loop {
+        let remaining = limit - data.len() as u64;
+        let start = data.len();
+        data.resize(start + (chunk_size as u64).min(remaining) as usize, 0);
+        reader.read_exact(&mut data[start..])?;
+        data[start..].iter_mut().for_each(|byte| *byte ^= key);
+
+        let mut scan = scan_index(&data, compressed);
+        let at_limit = data.len() as u64 == limit;
+        let truncated = matches!(scan.problems.last(), Some(IndexProblem::Truncated { .. }));
+        // 向后寻找合理记录时也可能因缓冲区不足而失败，未到上限时一律读完更多数据再扫描
+        let gave_up = scan.header_size.is_none() && !truncated;
+        if at_limit || (!truncated && !gave_up) {
+            if truncated && limit < file_len {
+                // 不是文件被截断，而是索引超出了上限
+                scan.problems.pop();
+                scan.problems.push(IndexProblem::TooLarge { limit });
+            }
+            return Ok((data, scan));
+        }
+        chunk_size *= 2;
+    }
+}
+
+/// 容错扫描一个可能损坏的PAK：布局和完整的索引
+#[derive(Debug, Clone)]
+pub struct PakScan {
+    /// 格式变体（无法确定时为扫描问题最少的布局）
+    pub variant: PakVariant,
+    /// 文件长度
+    pub file_len: u64,
+    /// 索引扫描结果
+    pub index: IndexScan,
+}
+
+impl PakScan {
+    /// 推算每条记录的数据偏移，无法确定时为 None
+    ///
+    /// 第一段记录从数据区起点向后排列。索引中间有跳过的记录时，后续段的数据位置无法直接得出：
+    /// 最后一段假定数据区正好在文件末尾结束，从末尾向前推算（不会早于第一段数据的结束位置），
+    /// 中间的段无法定位。文件被截断时这样推算出的位置是错的，见 [`PakScan::is_offset_inferred`]。
+    pub fn data_offsets(&self) -> Vec<Option<u64>> {
+        let records = &self.index.records;
+        let mut offsets = vec![None; records.len()];
+        let Some(header_size) = self.index.header_size else {
+            return offsets;
+        };
+
+        let mut offset = header_size;
+        for (index, record) in records.iter().enumerate().take_while(|(_, record)| record.segment == 0) {
+            offsets[index] = Some(offset);
+            offset += record.z_size as u64;
+        }
+
+        let first_segment_end = offset;
+        let last_segment = records.last().map_or(0, |record| record.segment);
+        if last_segment > 0 {
+            let mut end = self.file_len;
+            for (index, record) in records.iter().enumerate().rev().take_while(|(_, record)| record.segment == last_segment) {
+                let Some(start) = end.checked_sub(record.z_size as u64).filter(|&start| start >= first_segment_end) else {
+                    break;
+                };
+                offsets[index] = Some(start);
+                end = start;
+            }
+        }
+        offsets
+    }
+
+    /// 记录的数据偏移是否是从文件末尾向前推算的（只有跳过了索引记录之后的段才会这样推算）
+    pub fn is_offset_inferred(&self, record: &IndexRecord) -> bool {
+        record.segment > 0
+    }
+
+    /// 数据区的结束位置（索引完整且没有跳过的记录时才能确定）
+    pub fn data_end(&self) -> Option<u64> {
+        let header_size = self.index.header_size?;
+        if self.index.records.last().is_some_and(|record| record.segment > 0) {
+            return None;
+        }
+        Some(header_size + self.index.records.iter().map(|record| record.z_size as u64).sum::<u64>())
+    }
+}
+
+/// 容错扫描PAK数据：根据 magic 确定密钥，能正常探测时使用探测出的布局，
+/// 否则分别按压缩和未压缩布局扫描索引，选择问题较少的一个
+pub fn scan_pak<R: Read + Seek>(reader: &mut R) -> PakResult<PakScan> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    if file_len < 8 {
+        return Err(PakError::TruncatedIndex { offset: file_len });
+    }
+
+    let mut magic_bytes = [0u8; 4];
+    reader.seek(SeekFrom::Start(0))?;
+    reader.read_exact(&mut magic_bytes)?;
+    let Some(key) = magic_key(magic_bytes) else {
+        return Err(PakError::BadMagic { found: u32::from_le_bytes(magic_bytes) });
+    };
+
+    let (data, index, compressed) = match probe(reader) {
+        Ok(variant) => {
+            let (data, index) = read_index_region(reader, key, variant.compressed)?;
+            (data, index, variant.compressed)
+        }
+        Err(_) => {
+            let (data, plain) = read_index_region(reader, key, false)?;
+            let (_, packed) = read_index_region(reader, key, true)?;
+            if packed.problems.len() < plain.problems.len() {
+                (data, packed, true)
+            } else {
+                (data, plain, false)
+            }
+        }
+    };
+
+    let variant = PakVariant {
+        xor_key: if key == 0 { None } else { Some(key) },
+        compressed,
+        version: u32::from_le_bytes([data[4], data[5], data[6], data[7]]),
+    };
+    Ok(PakScan { variant, file_len, index })
+}
+
+/// 每条记录在文件名之后的字节数（大小字段和时间戳）
+fn record_tail_len(compressed: bool) -> usize {
+    if compressed { 4 + 4 + 8 } else { 4 + 8 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::PakWriter;
+
+    /// 未加密、未压缩的PAK（文件名长度相同，每条记录 2 + 5 + 12 字节）
+    fn plain_pak(names: &[&str]) -> Vec<u8> {
+        let mut writer = PakWriter::new().encrypted(false);
+        for name in names {
+            writer.add_bytes(name, name.as_bytes().to_vec()).unwrap();
+        }
+        let mut bytes = Vec::new();
+        writer.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    const RECORD_LEN: usize = 2 + 5 + 12;
+
+    #[test]
+    fn intact_index_is_read_in_one_segment() {
+        let data = plain_pak(&["a.txt", "b.txt"]);
+        let scan = scan_index(&data, false);
+
+        assert!(scan.problems.is_empty());
+        assert_eq!(scan.records.len(), 2);
+        assert_eq!(scan.header_size, Some((8 + 2 * RECORD_LEN + 1) as u64));
+    }
+
+    #[test]
+    fn damaged_flag_is_read_as_zero() {
+        let mut data = plain_pak(&["a.txt", "b.txt"]);
+        data[8 + RECORD_LEN] = 0x13;
+        let scan = scan_index(&data, false);
+
+        assert_eq!(scan.problems, [IndexProblem::DamagedFlag { offset: (8 + RECORD_LEN) as u64, flag: 0x13 }]);
+        assert_eq!(scan.records.len(), 2);
+        assert!(scan.records.iter().all(|record| record.segment == 0));
+    }
+
+    #[test]
+    fn bad_record_is_skipped_and_scan_resumes_at_next_plausible_record() {
+        let mut data = plain_pak(&["a.txt", "b.txt", "c.txt"]);
+        let bad = 8 + RECORD_LEN;
+        data[bad + 2] = 0x01;
+        let scan = scan_index(&data, false);
+
+        assert_eq!(scan.problems, [IndexProblem::BadRecord {
+            offset: bad as u64,
+            flag: 0,
+            resumed_at: Some((bad + RECORD_LEN) as u64),
+        }]);
+        let names: Vec<_> = scan.records.iter().map(|record| (record.raw_name.as_slice(), record.segment)).collect();
+        assert_eq!(names, [(&b"a.txt"[..], 0), (&b"c.txt"[..], 1)]);
+        assert!(scan.header_size.is_some());
+    }
+
+    #[test]
+    fn index_cut_short_is_reported_as_truncated() {
+        let data = plain_pak(&["a.txt", "b.txt"]);
+        let scan = scan_index(&data[..8 + RECORD_LEN + 4], false);
+
+        assert_eq!(scan.problems, [IndexProblem::Truncated { offset: (8 + RECORD_LEN) as u64 }]);
+        assert_eq!(scan.records.len(), 1);
+    }
+
+    #[test]
+    fn offsets_after_a_skipped_record_are_inferred_from_the_end() {
+        let mut data = plain_pak(&["a.txt", "b.txt", "c.txt"]);
+        data[8 + RECORD_LEN + 2] = 0x01;
+        let file_len = data.len() as u64;
+        let scan = scan_pak(&mut io::Cursor::new(data)).unwrap();
+
+        let offsets = scan.data_offsets();
+        let header_size = scan.index.header_size.unwrap();
+        assert_eq!(offsets, [Some(header_size), Some(file_len - 5)]);
+        assert!(!scan.is_offset_inferred(&scan.index.records[0]));
+        assert!(scan.is_offset_inferred(&scan.index.records[1]));
+        assert_eq!(scan.data_end(), None);
+    }
+
+    /// magic 之后的内容全是同一个字节的虚拟文件，只统计读取了多少字节
+    struct FilledFile {
+        fill: u8,
+        len: u64,
+        pos: u64,
+        read: u64,
+    }
+
+    impl FilledFile {
+        fn new(fill: u8, len: u64) -> Self {
+            Self { fill, len, pos: 0, read: 0 }
+        }
+    }
+
+    impl Read for FilledFile {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let count = (buf.len() as u64).min(self.len.saturating_sub(self.pos)) as usize;
+            let magic = PakInfo::MAGIC.to_le_bytes();
+            for (index, byte) in buf[..count].iter_mut().enumerate() {
+                *byte = magic.get(self.pos as usize + index).copied().unwrap_or(self.fill);
+            }
+            self.pos += count as u64;
+            self.read += count as u64;
+            Ok(count)
+        }
+    }
+
+    impl Seek for FilledFile {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.pos = match pos {
+                SeekFrom::Start(offset) => offset,
+                SeekFrom::End(offset) => self.len.saturating_add_signed(offset),
+                SeekFrom::Current(offset) => self.pos.saturating_add_signed(offset),
+            };
+            Ok(self.pos)
+        }
+    }
+
+    const LIMIT: u64 = 1 << 20;
+
+    #[test]
+    fn unparsable_index_is_read_only_up_to_the_limit() {
+        let mut file = FilledFile::new(0x41, 8 * LIMIT);
+        let (data, scan) = read_index_region_with_limit(&mut file, 0, false, LIMIT).unwrap();
+
+        assert_eq!(data.len() as u64, LIMIT);
+        assert!(file.read <= LIMIT);
+        assert!(scan.header_size.is_none());
+    }
+
+    #[test]
+    fn index_without_end_flag_before_the_limit_is_too_large() {
+        let mut file = FilledFile::new(0, 8 * LIMIT);
+        let (data, scan) = read_index_region_with_limit(&mut file, 0, false, LIMIT).unwrap();
+
+        assert_eq!(data.len() as u64, LIMIT);
+        assert_eq!(scan.problems.last(), Some(&IndexProblem::TooLarge { limit: LIMIT }));
+    }
+
+    #[test]
+    fn index_cut_short_by_the_end_of_the_file_is_truncated() {
+        let mut file = FilledFile::new(0, LIMIT / 2);
+        let (_, scan) = read_index_region_with_limit(&mut file, 0, false, LIMIT).unwrap();
+
+        assert!(matches!(scan.problems.last(), Some(IndexProblem::Truncated { .. })));
+    }
+}
@@ -1,100 +1,155 @@
-use std::fs::{self, File};
-use std::io::{self, Write};
-use std::path::Path;
-use crate::pak::{parse_pak_info, show_pak_info_simple};
-use crate::utils::{ensure_directory_exists, is_directory_empty, crypt_data};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::encoding::NameEncoding;
+use crate::entry_path::{sanitize_entry_path, PathLayout};
+use crate::error::PakError;
+use crate::filter::EntryFilter;
+use crate::manifest::Manifest;
+use crate::overwrite::{write_file_with_policy, OverwritePolicy, WriteOutcome, WriteStats};
+use crate::pak::show_pak_info_simple;
+use crate::probe::is_pak_file;
+use crate::reader::PakReader;
+use crate::utils::{ensure_directory_exists, is_directory_empty};
+
+/// 解包选项
+#[derive(Debug, Clone, Default)]
+pub struct UnpackOptions {
+    /// 文件名编码（Auto 时自动识别）
+    pub encoding: NameEncoding,
+    /// 写入解包清单的路径，None 表示不写清单
+    pub manifest: Option<PathBuf>,
+    /// 只解包筛选出的条目
+    pub filter: EntryFilter,
+    /// 输出文件已存在时的处理方式（Refuse 时要求输出目录为空）
+    pub overwrite: OverwritePolicy,
+    /// 条目在输出目录中的布局
+    pub layout: PathLayout,
+}
 
 /// 解包PAK文件到指定目录
 pub fn unpack_pak(input_path: &Path, output_dir: &Path) -> io::Result<()> {
+    unpack_pak_with_options(input_path, output_dir, &UnpackOptions::default())
+}
+
+/// 解包PAK文件到指定目录（带选项）
+pub fn unpack_pak_with_options(input_path: &Path, output_dir: &Path, options: &UnpackOptions) -> io::Result<()> {
     // 验证输入文件
     if !input_path.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("输入文件不存在: {}", input_path.display())
-        ));
+        return Err(PakError::InputNotFound { path: input_path.to_path_buf() }.into());
     }
     
-    if !input_path.extension().map_or(false, |ext| ext == "pak") {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "输入文件必须是 .pak 文件"
-        ));
+    // 按文件头识别PAK，不依赖扩展名
+    if !is_pak_file(input_path) {
+        return Err(PakError::NotPakData { path: input_path.to_path_buf() }.into());
     }
     
-    // 验证输出目录
-    if output_dir.exists() && !is_directory_empty(output_dir)? {
-        return Err(io::Error::new(
-            io::ErrorKind::AlreadyExists,
-            format!("输出目录不为空: {}", output_dir.display())
-        ));
+    // 验证输出目录（指定了覆盖策略时允许解包到已有目录）
+    if options.overwrite == OverwritePolicy::Refuse && output_dir.exists() && !is_directory_empty(output_dir)? {
+        return Err(PakError::OutputNotEmpty { path: output_dir.to_path_buf() }.into());
     }
     
+    // 打开PAK文件（只解析头部，条目数据按需读取），并在创建输出目录前检查筛选条件
+    let mut reader = PakReader::open_with_encoding(input_path, options.encoding)?;
+    let file_count = reader.files().len();
+    let selection = options.filter.select(reader.files())?;
+    
     // 创建输出目录
     fs::create_dir_all(output_dir)?;
     
     println!("正在解包: {}", input_path.display());
     println!("输出目录: {}", output_dir.display());
     
-    // 读取PAK文件
-    let mut data = fs::read(input_path)?;
-    
-    // 检测是否加密
-    let encrypted = detect_encryption(&data);
+    println!("PAK 文件信息:");
+    show_pak_info_simple(reader.file_len(), reader.is_encrypted(), reader.files());
+    println!("  文件名编码: {}", reader.encoding());
+    println!();
     
-    if encrypted {
-        crypt_data(&mut data);
+    // 只读取筛选出的条目
+    for name in &selection.missing {
+        println!("警告: 列表中的条目不存在: {}", name);
     }
+    if !options.filter.is_empty() {
+        println!("筛选出 {} / {} 个条目", selection.indices.len(), file_count);
+    }
+    let selected_count = selection.indices.len();
     
-    // 解析PAK信息
-    let (pak_info, header_size) = parse_pak_info(&data)?;
-    
-    println!("PAK 文件信息:");
-    show_pak_info_simple(&data, encrypted, &pak_info.file_info_library);
-    println!();
+    // 输出目录为空时只有PAK内重名的条目会互相覆盖，保持后者覆盖前者
+    let file_policy = match options.overwrite {
+        OverwritePolicy::Refuse => OverwritePolicy::Force,
+        policy => policy,
+    };
     
     // 提取文件
-    let mut file_offset = header_size;
-    for (index, file_info) in pak_info.file_info_library.iter().enumerate() {
-        if index % 100 == 0 {
-            println!("正在解包: {}/{}", index + 1, pak_info.file_info_library.len());
+    let mut stats = WriteStats::default();
+    let mut rewritten_count = 0;
+    let mut rejected_count = 0;
+    let mut shallow_count = 0;
+    let mut written_paths = HashSet::new();
+    for (position, index) in selection.indices.into_iter().enumerate() {
+        if position % 100 == 0 {
+            println!("正在解包: {}/{}", position + 1, selected_count);
         }
         
-        // 检查数据边界
-        if file_offset + file_info.z_size as usize > data.len() {
-            return Err(io::Error::new(
-                io::ErrorKind::UnexpectedEof,
-                format!("文件 {} 数据超出PAK文件边界", file_info.file_name)
-            ));
+        // 清理条目路径，保证输出不会离开目标目录
+        let file_name = &reader.files()[index].file_name;
+        let Some(safe_path) = sanitize_entry_path(file_name) else {
+            println!("警告: 跳过不安全的条目路径: {}", file_name);
+            rejected_count += 1;
+            continue;
+        };
+        if safe_path.is_rewritten() {
+            println!("警告: 条目路径已改写: {} -> {}（{}）", file_name, safe_path.relative.display(), safe_path.reason_text());
+            rewritten_count += 1;
         }
         
-        // 读取文件数据
-        let file_data = &data[file_offset..file_offset + file_info.z_size as usize];
+        // 按布局调整输出路径
+        let Some(relative) = options.layout.apply(&safe_path.relative) else {
+            shallow_count += 1;
+            continue;
+        };
+        let output_file_path = output_dir.join(relative);
+        if !written_paths.insert(output_file_path.clone()) {
+            println!("警告: 多个条目写入同一路径: {}（{}）", output_file_path.display(), file_name);
+        }
         
-        // 创建输出文件路径
-        let output_file_path = output_dir.join(&file_info.file_name);
+        // 读取文件数据（压缩模式下解压）
+        let file_data = reader.read_entry(index)?;
         ensure_directory_exists(&output_file_path)?;
         
-        // 写入文件
-        let mut output_file = File::create(&output_file_path)?;
-        output_file.write_all(file_data)?;
-        
-        file_offset += file_info.z_size as usize;
+        // 按覆盖策略写入文件，并还原PAK中记录的修改时间
+        let outcome = write_file_with_policy(&output_file_path, &file_data, reader.files()[index]._file_time, file_policy)?;
+        if let WriteOutcome::BackedUp(backup_path) = &outcome {
+            println!("已备份: {} -> {}", output_file_path.display(), backup_path.display());
+        }
+        stats.record(&outcome);
     }
     
-    println!("解包完成！提取了 {} 个文件", pak_info.file_info_library.len());
-    Ok(())
-}
-
-/// 检测PAK文件是否加密
-fn detect_encryption(data: &[u8]) -> bool {
-    if data.len() < 8 {
-        return false;
+    println!("解包完成！提取了 {} 个文件", stats.written());
+    if options.overwrite != OverwritePolicy::Refuse {
+        println!(
+            "新建 {}，覆盖 {}，备份后覆盖 {}，跳过已存在 {}",
+            stats.created, stats.overwritten, stats.backed_up, stats.skipped
+        );
+    }
+    if rewritten_count > 0 {
+        println!("改写了 {} 个条目路径", rewritten_count);
+    }
+    if rejected_count > 0 {
+        println!("跳过了 {} 个不安全的条目", rejected_count);
+    }
+    if shallow_count > 0 {
+        println!("跳过了 {} 个目录层级不足的条目", shallow_count);
     }
     
-    // 检查magic number
-    let magic = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
-    
-    // PC版加密后的magic是0x4D37BD37 (1295498551)
-    // 解密后应该是0xBAC04AC0
-    magic == 0x4D37BD37
-} 
\ No newline at end of file
+    // 写入清单，供重新打包时还原顺序和元数据
+    if let Some(manifest_path) = &options.manifest {
+        if options.layout != PathLayout::Full {
+            println!("警告: 解包布局改变了条目路径，重新打包前需要还原目录结构");
+        }
+        Manifest::from_reader(&mut reader)?.write_to_path(manifest_path)?;
+        println!("已写入清单: {}", manifest_path.display());
+    }
+    Ok(())
+}
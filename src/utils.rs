@@ -1,7 +1,12 @@
-use std::fs;
-use std::io::{self, Write};
-use std::path::Path;
-use encoding_rs::GBK;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use crate::error::{PakError, PakResult};
 
 /// 确保目录存在（创建父目录）
 pub fn ensure_directory_exists(file_path: &Path) -> io::Result<()> {
@@ -21,38 +26,28 @@ pub fn is_directory_empty(dir: &Path) -> io::Result<bool> {
     Ok(entries.next().is_none())
 }
 
-/// 读取字符串（长度前缀为u8）
-pub fn read_string_by_u8_head(data: &[u8], pos: &mut usize) -> io::Result<String> {
+/// 读取字节串（长度前缀为u8），返回原始字节
+pub fn read_bytes_by_u8_head<'a>(data: &'a [u8], pos: &mut usize) -> PakResult<&'a [u8]> {
     if *pos >= data.len() {
-        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Unexpected end of data"));
+        return Err(PakError::TruncatedIndex { offset: *pos as u64 });
     }
     
     let length = data[*pos] as usize;
     *pos += 1;
     
     if *pos + length > data.len() {
-        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "String length exceeds data"));
+        return Err(PakError::TruncatedIndex { offset: *pos as u64 });
     }
     
-    let string_bytes = &data[*pos..*pos + length];
+    let bytes = &data[*pos..*pos + length];
     *pos += length;
-    
-    // 使用GBK (ANSI) 编码解析，与PopStudio保持一致
-    let (decoded, _, _) = GBK.decode(string_bytes);
-    Ok(decoded.to_string())
+    Ok(bytes)
 }
 
-/// 写入字符串（长度前缀为u8）
-pub fn write_string_by_u8_head(writer: &mut dyn Write, s: &str) -> io::Result<()> {
-    // 使用GBK (ANSI) 编码，与PopStudio保持一致
-    let (encoded, _, _) = GBK.encode(s);
-    let bytes = encoded.as_ref();
-    
+/// 写入已编码的字节串（长度前缀为u8），name 用于错误信息
+pub fn write_bytes_by_u8_head(writer: &mut dyn Write, bytes: &[u8], name: &str) -> PakResult<()> {
     if bytes.len() > 255 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            format!("String too long: {} bytes (max 255)", bytes.len())
-        ));
+        return Err(PakError::NameTooLong { name: name.to_string(), len: bytes.len() });
     }
     
     writer.write_all(&[bytes.len() as u8])?;
@@ -61,9 +56,9 @@ pub fn write_string_by_u8_head(writer: &mut dyn Write, s: &str) -> io::Result<()
 }
 
 /// 读取小端序u32
-pub fn read_u32_le(data: &[u8], pos: &mut usize) -> io::Result<u32> {
+pub fn read_u32_le(data: &[u8], pos: &mut usize) -> PakResult<u32> {
     if *pos + 4 > data.len() {
-        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Cannot read u32"));
+        return Err(PakError::TruncatedIndex { offset: *pos as u64 });
     }
     
     let value = u32::from_le_bytes([
@@ -77,9 +72,9 @@ pub fn read_u32_le(data: &[u8], pos: &mut usize) -> io::Result<u32> {
 }
 
 /// 读取小端序u64
-pub fn read_u64_le(data: &[u8], pos: &mut usize) -> io::Result<u64> {
+pub fn read_u64_le(data: &[u8], pos: &mut usize) -> PakResult<u64> {
     if *pos + 8 > data.len() {
-        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Cannot read u64"));
+        return Err(PakError::TruncatedIndex { offset: *pos as u64 });
     }
     
     let value = u64::from_le_bytes([
@@ -96,10 +91,193 @@ pub fn read_u64_le(data: &[u8], pos: &mut usize) -> io::Result<u64> {
     Ok(value)
 }
 
+/// PC版PAK的异或密钥
+pub const CRYPT_KEY: u8 = 0xF7;
+
 /// 数据处理（PC版PAK格式转换）
 pub fn crypt_data(data: &mut [u8]) {
-    const KEY: u8 = 0xF7;
     for byte in data.iter_mut() {
-        *byte ^= KEY;
+        *byte ^= CRYPT_KEY;
+    }
+} 
+/// 解压zlib数据（expected_size 用于预分配，大小校验由调用方负责）
+pub fn decompress_data(data: &[u8], expected_size: u32) -> io::Result<Vec<u8>> {
+    let mut decoder = ZlibDecoder::new(data);
+    let mut output = Vec::with_capacity(expected_size as usize);
+    decoder.read_to_end(&mut output)?;
+    Ok(output)
+}
+
+/// 使用zlib压缩数据（level: 0-9）
+pub fn compress_data(data: &[u8], level: u32) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// FILETIME（1601-01-01起的100纳秒间隔数）与Unix纪元之间的差值
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+/// Windows FILETIME 转换为 SystemTime
+pub fn filetime_to_system_time(file_time: u64) -> SystemTime {
+    if file_time >= FILETIME_UNIX_EPOCH {
+        let ticks = file_time - FILETIME_UNIX_EPOCH;
+        UNIX_EPOCH + Duration::new(ticks / 10_000_000, (ticks % 10_000_000) as u32 * 100)
+    } else {
+        let ticks = FILETIME_UNIX_EPOCH - file_time;
+        UNIX_EPOCH - Duration::new(ticks / 10_000_000, (ticks % 10_000_000) as u32 * 100)
+    }
+}
+
+/// SystemTime 转换为 Windows FILETIME
+pub fn system_time_to_filetime(time: SystemTime) -> u64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => FILETIME_UNIX_EPOCH.saturating_add(duration_to_ticks(after)),
+        Err(before) => FILETIME_UNIX_EPOCH.saturating_sub(duration_to_ticks(before.duration())),
+    }
+}
+
+/// 读取环境变量 SOURCE_DATE_EPOCH（Unix秒数），返回对应的 FILETIME
+///
+/// 见 https://reproducible-builds.org/specs/source-date-epoch/
+pub fn source_date_epoch() -> PakResult<Option<u64>> {
+    let Some(value) = std::env::var_os("SOURCE_DATE_EPOCH") else {
+        return Ok(None);
+    };
+    let value = value.to_string_lossy().into_owned();
+    let secs: u64 = value.trim().parse().map_err(|_| PakError::InvalidSourceDateEpoch { value: value.clone() })?;
+    secs.checked_mul(10_000_000)
+        .and_then(|ticks| ticks.checked_add(FILETIME_UNIX_EPOCH))
+        .map(Some)
+        .ok_or(PakError::InvalidSourceDateEpoch { value })
+}
+
+fn duration_to_ticks(duration: Duration) -> u64 {
+    duration.as_secs().saturating_mul(10_000_000) + (duration.subsec_nanos() / 100) as u64
+}
+
+/// 格式化 FILETIME 为 "YYYY-MM-DD HH:MM:SS"（UTC）
+pub fn format_filetime(file_time: u64) -> String {
+    let secs = (file_time / 10_000_000) as i64 - (FILETIME_UNIX_EPOCH / 10_000_000) as i64;
+    let days = secs.div_euclid(86_400);
+    let secs_of_day = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year, month, day,
+        secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60
+    )
+}
+
+/// 将Unix纪元以来的天数转换为公历日期（年、月、日）
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// 将文件数据写入指定路径，并把修改时间设置为 FILETIME 对应的时间
+pub fn write_file_with_time(path: &Path, data: &[u8], file_time: u64) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(data)?;
+    file.set_modified(filetime_to_system_time(file_time))?;
+    Ok(())
+}
+
+/// 原子地写入文件：先写到同一目录下的临时文件，fsync 后再改名为目标路径
+///
+/// 写入失败时删除临时文件，目标路径保持原样；进程中途被终止时最多留下一个临时文件，
+/// 不会出现只写了一半的目标文件。`replace` 为 false 时用硬链接放置结果，
+/// 目标在写入期间被创建时不会被替换，而是返回 `PakError::OutputExists`。
+pub fn write_file_atomically<T>(
+    path: &Path,
+    replace: bool,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<T>,
+) -> io::Result<T> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+    let (temp_path, file) = create_temp_file(dir, &file_name)?;
+
+    let result = (|| {
+        let mut writer = BufWriter::new(file);
+        let value = write(&mut writer)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        drop(file);
+
+        if replace {
+            fs::rename(&temp_path, path)?;
+        } else {
+            place_new_file(&temp_path, path)?;
+        }
+        Ok(value)
+    })();
+
+    match result {
+        Ok(value) => {
+            // 让改名本身也落盘（不支持打开目录的平台上忽略）
+            if let Ok(dir_file) = File::open(dir) {
+                let _ = dir_file.sync_all();
+            }
+            Ok(value)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+/// 在目录中创建新的临时文件
+///
+/// 文件名包含进程号、时间和计数器；与之前中断的运行留下的文件重名时换一个名字重试。
+fn create_temp_file(dir: &Path, file_name: &str) -> io::Result<(PathBuf, File)> {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    const MAX_ATTEMPTS: u32 = 100;
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    let mut attempt = 0;
+    loop {
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temp_path = dir.join(format!(".{}.{}-{:08x}-{}.tmp", file_name, std::process::id(), nanos, count));
+        match fs::OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt + 1 < MAX_ATTEMPTS => attempt += 1,
+            Err(e) => return Err(e),
+        }
     }
-} 
\ No newline at end of file
+}
+
+/// 把临时文件放到目标路径，目标已存在时返回 `PakError::OutputExists` 而不是替换它
+///
+/// 硬链接在目标已存在时失败，检查和放置之间不会被其他进程抢先；
+/// 不支持硬链接的文件系统（如 FAT）上退回为检查后改名。
+fn place_new_file(temp_path: &Path, path: &Path) -> io::Result<()> {
+    match fs::hard_link(temp_path, path) {
+        Ok(()) => {
+            // 结果已经就位，临时文件名删不掉也不影响输出
+            let _ = fs::remove_file(temp_path);
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            Err(PakError::OutputExists { path: path.to_path_buf() }.into())
+        }
+        Err(_) => {
+            if path.exists() {
+                return Err(PakError::OutputExists { path: path.to_path_buf() }.into());
+            }
+            fs::rename(temp_path, path)
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use colored::*;
+use crate::encoding::NameEncoding;
+use crate::error::PakError;
+use crate::pak::{decode_entry_data, FileInfo};
+use crate::probe::PakVariant;
+use crate::reader::{normalize_entry_name, PakReader};
+use crate::scan::{scan_pak, IndexProblem, IndexScan, PakScan};
+
+/// 校验项（按汇总表中的顺序排列）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VerifyCheck {
+    /// 文件长度和 magic
+    Header,
+    /// 索引记录的标志不是 0x00 或 0x80，或者记录的其余部分损坏
+    IndexRecord,
+    /// 索引在结束标志之前被截断
+    TruncatedIndex,
+    /// 空文件名
+    EmptyName,
+    /// 文件名不能按所选编码解码
+    NameEncoding,
+    /// 完全相同的条目名
+    DuplicateName,
+    /// 只有大小写或分隔符不同的条目名
+    CaseCollision,
+    /// 条目数据超出文件末尾
+    OutOfBounds,
+    /// 解压失败或解压后大小不符
+    CorruptData,
+    /// 数据区之后的多余字节
+    TrailingData,
+}
+
+impl VerifyCheck {
+    /// 所有校验项
+    pub const ALL: [VerifyCheck; 10] = [
+        VerifyCheck::Header,
+        VerifyCheck::IndexRecord,
+        VerifyCheck::TruncatedIndex,
+        VerifyCheck::EmptyName,
+        VerifyCheck::NameEncoding,
+        VerifyCheck::DuplicateName,
+        VerifyCheck::CaseCollision,
+        VerifyCheck::OutOfBounds,
+        VerifyCheck::CorruptData,
+        VerifyCheck::TrailingData,
+    ];
+}
+
+impl fmt::Display for VerifyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            VerifyCheck::Header => "文件头",
+            VerifyCheck::IndexRecord => "索引记录",
+            VerifyCheck::TruncatedIndex => "索引截断",
+            VerifyCheck::EmptyName => "空文件名",
+            VerifyCheck::NameEncoding => "文件名编码",
+            VerifyCheck::DuplicateName => "重复条目名",
+            VerifyCheck::CaseCollision => "大小写冲突",
+            VerifyCheck::OutOfBounds => "数据越界",
+            VerifyCheck::CorruptData => "数据损坏",
+            VerifyCheck::TrailingData => "多余数据",
+        };
+        f.write_str(label)
+    }
+}
+
+/// 校验中发现的问题
+#[derive(Debug, Clone)]
+pub struct VerifyIssue {
+    /// 问题所属的校验项
+    pub check: VerifyCheck,
+    /// 出问题的条目（None 表示整个PAK的问题）
+    pub entry: Option<String>,
+    /// 问题描述
+    pub message: String,
+}
+
+/// 校验结果
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    /// 检查所用的格式变体（文件头无效时为 None）
+    pub variant: Option<PakVariant>,
+    /// 检查的条目数量
+    pub entry_count: usize,
+    /// 发现的所有问题
+    pub issues: Vec<VerifyIssue>,
+}
+
+impl VerifyReport {
+    /// 是否没有发现任何问题
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// 某个校验项下的问题数量
+    pub fn count(&self, check: VerifyCheck) -> usize {
+        self.issues.iter().filter(|issue| issue.check == check).count()
+    }
+
+    fn push(&mut self, check: VerifyCheck, entry: Option<String>, message: String) {
+        self.issues.push(VerifyIssue { check, entry, message });
+    }
+}
+
+/// 校验已打开的PAK中的所有条目
+pub fn verify_pak<R: Read + Seek>(reader: &mut PakReader<R>) -> io::Result<VerifyReport> {
+    let encoding = reader.encoding();
+    verify_stream(reader.get_mut(), encoding)
+}
+
+/// 对PAK数据做结构校验，收集全部问题而不是在第一个错误处停止
+///
+/// 与 [`PakReader`] 不同，索引损坏时也会继续：遇到无效标志后从下一条看起来合理的记录恢复扫描，
+/// 因此一次运行可以报告所有损坏的位置。无法确定压缩布局时选择问题最少的布局。
+pub fn verify_stream<R: Read + Seek>(reader: &mut R, encoding: NameEncoding) -> io::Result<VerifyReport> {
+    let mut report = VerifyReport::default();
+    let scan = match scan_pak(reader) {
+        Ok(scan) => scan,
+        Err(e @ (PakError::TruncatedIndex { .. } | PakError::BadMagic { .. })) => {
+            report.push(VerifyCheck::Header, None, e.to_string());
+            return Ok(report);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    report.variant = Some(scan.variant);
+    report.entry_count = scan.index.records.len();
+
+    check_index(&scan.index, &mut report);
+    let files = check_names(&scan.index, encoding, &mut report);
+    if scan.index.header_size.is_some() {
+        check_data(reader, &scan, &files, &mut report)?;
+    }
+
+    Ok(report)
+}
+
+/// 校验PAK文件并输出结果，返回是否通过
+pub fn run_verify(pak_path: &Path, encoding: NameEncoding) -> io::Result<bool> {
+    let mut reader = BufReader::new(File::open(pak_path)?);
+    let report = verify_stream(&mut reader, encoding)?;
+    for line in format_verify_report(&report) {
+        println!("{}", line);
+    }
+    Ok(report.is_ok())
+}
+
+/// 把校验结果格式化为输出行：逐条问题、按校验项的汇总表和结论
+pub fn format_verify_report(report: &VerifyReport) -> Vec<String> {
+    let mut lines = Vec::new();
+    for issue in &report.issues {
+        match &issue.entry {
+            Some(entry) => lines.push(format!("{} [{}] {}: {}", "✗".red(), issue.check, entry, issue.message)),
+            None => lines.push(format!("{} [{}] {}", "✗".red(), issue.check, issue.message)),
+        }
+    }
+    if !report.issues.is_empty() {
+        lines.push(String::new());
+    }
+
+    if let Some(variant) = report.variant {
+        lines.push(format!("格式: {}", variant));
+    }
+    lines.push(format!("{}  {}", pad_to_width("校验项", 12), "结果"));
+    for check in VerifyCheck::ALL {
+        let count = report.count(check);
+        let result = if count == 0 {
+            "✓".green().to_string()
+        } else {
+            format!("✗ {}", count).red().to_string()
+        };
+        lines.push(format!("{}  {}", pad_to_width(&check.to_string(), 12), result));
+    }
+    lines.push(String::new());
+
+    if report.is_ok() {
+        lines.push(format!("{}", format!("校验通过: {} 个条目", report.entry_count).green()));
+    } else {
+        lines.push(format!("{}", format!("校验失败: {} 个条目中发现 {} 个问题", report.entry_count, report.issues.len()).red()));
+    }
+    lines
+}
+
+/// 报告扫描索引时发现的结构问题
+fn check_index(scan: &IndexScan, report: &mut VerifyReport) {
+    for problem in &scan.problems {
+        match *problem {
+            IndexProblem::DamagedFlag { offset, flag } => {
+                report.push(VerifyCheck::IndexRecord, None, format!(
+                    "偏移 0x{:X} 处的条目标志无效: 0x{:02X}，记录其余部分完整", offset, flag
+                ));
+            }
+            IndexProblem::BadRecord { offset, flag, resumed_at } => {
+                let problem = if flag == 0 {
+                    format!("偏移 0x{:X} 处的记录无效（文件名或大小字段损坏）", offset)
+                } else {
+                    format!("偏移 0x{:X} 处的条目标志无效: 0x{:02X}", offset, flag)
+                };
+                let message = match resumed_at {
+                    Some(next) => format!("{}，跳过 {} 字节后从 0x{:X} 继续", problem, next - offset, next),
+                    None => format!("{}，之后找不到可以恢复的记录", problem),
+                };
+                report.push(VerifyCheck::IndexRecord, None, message);
+            }
+            IndexProblem::Truncated { offset } => {
+                report.push(VerifyCheck::TruncatedIndex, None, format!("索引在偏移 0x{:X} 处被截断（缺少结束标志）", offset));
+            }
+            IndexProblem::TooLarge { limit } => {
+                report.push(VerifyCheck::TruncatedIndex, None, format!("索引超过 {} 字节的上限（缺少结束标志），之后的部分没有扫描", limit));
+            }
+        }
+    }
+}
+
+/// 检查文件名（空名、编码、重名），返回解码后的条目信息
+fn check_names(scan: &IndexScan, encoding: NameEncoding, report: &mut VerifyReport) -> Vec<FileInfo> {
+    let encoding = encoding.detect(scan.records.iter().map(|record| record.raw_name.as_slice()));
+    let mut exact: HashMap<String, usize> = HashMap::new();
+    let mut normalized: HashMap<String, String> = HashMap::new();
+    let mut files = Vec::with_capacity(scan.records.len());
+
+    for (index, record) in scan.records.iter().enumerate() {
+        let file_name = encoding.decode(&record.raw_name);
+        let label = if file_name.is_empty() {
+            format!("#{}（偏移 0x{:X}）", index, record.offset)
+        } else {
+            file_name.clone()
+        };
+
+        if record.raw_name.is_empty() {
+            report.push(VerifyCheck::EmptyName, Some(label.clone()), "文件名为空".to_string());
+        } else if !encoding.decodes_cleanly(&record.raw_name) {
+            report.push(VerifyCheck::NameEncoding, Some(label.clone()), format!(
+                "文件名不是有效的 {}: {}",
+                encoding,
+                record.raw_name.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ")
+            ));
+        }
+
+        if !file_name.is_empty() {
+            if let Some(&first) = exact.get(&file_name) {
+                report.push(VerifyCheck::DuplicateName, Some(label.clone()), format!("与第 {} 个条目同名", first + 1));
+            } else if let Some(other) = normalized.get(&normalize_entry_name(&file_name)) {
+                report.push(VerifyCheck::CaseCollision, Some(label.clone()), format!("与 {} 只有大小写或分隔符不同", other));
+            }
+            exact.entry(file_name.clone()).or_insert(index);
+            normalized.entry(normalize_entry_name(&file_name)).or_insert(file_name.clone());
+        }
+
+        files.push(FileInfo {
+            file_name: label,
+            z_size: record.z_size,
+            _size: record.size,
+            _file_time: record.file_time,
+            raw_name: Some(record.raw_name.clone()),
+        });
+    }
+    files
+}
+
+/// 检查每个条目的数据边界和内容，以及数据区之后的多余字节
+fn check_data<R: Read + Seek>(reader: &mut R, scan: &PakScan, files: &[FileInfo], report: &mut VerifyReport) -> io::Result<()> {
+    let key = scan.variant.key();
+    let mut overflowing = Vec::new();
+    let mut unlocated = 0;
+
+    for (file_info, offset) in files.iter().zip(scan.data_offsets()) {
+        let Some(offset) = offset else {
+            unlocated += 1;
+            continue;
+        };
+        if offset + file_info.z_size as u64 > scan.file_len {
+            overflowing.push((file_info, offset));
+        } else if scan.variant.compressed {
+            let mut raw = vec![0u8; file_info.z_size as usize];
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(&mut raw)?;
+            raw.iter_mut().for_each(|byte| *byte ^= key);
+            if let Err(e) = decode_entry_data(&raw, file_info, true) {
+                report.push(VerifyCheck::CorruptData, Some(file_info.file_name.clone()), e.to_string());
+            }
+        }
+    }
+
+    // 一个错误的大小会让之后的条目全部越界，只详细报告第一个
+    if let Some(&(file_info, start)) = overflowing.first() {
+        report.push(VerifyCheck::OutOfBounds, Some(file_info.file_name.clone()), format!(
+            "数据范围 0x{:X}..0x{:X}（{} 字节）超出文件长度 {}",
+            start, start + file_info.z_size as u64, file_info.z_size, scan.file_len
+        ));
+        if overflowing.len() > 1 {
+            report.push(VerifyCheck::OutOfBounds, None, format!("之后还有 {} 个条目的数据超出文件末尾", overflowing.len() - 1));
+        }
+    }
+    if unlocated > 0 {
+        report.push(VerifyCheck::OutOfBounds, None, format!("索引中有记录被跳过，{} 个条目的数据位置无法确定", unlocated));
+    }
+
+    if let Some(data_end) = scan.data_end().filter(|&end| end < scan.file_len) {
+        report.push(VerifyCheck::TrailingData, None, format!(
+            "数据区在 0x{:X} 结束，之后有 {} 字节多余数据", data_end, scan.file_len - data_end
+        ));
+    }
+    Ok(())
+}
+
+/// 按显示宽度补齐空格（中文字符占两列）
+fn pad_to_width(text: &str, width: usize) -> String {
+    let text_width: usize = text.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum();
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text_width)))
+}
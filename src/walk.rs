@@ -0,0 +1,137 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+#[cfg(not(unix))]
+use std::path::PathBuf;
+use std::str::FromStr;
+use crate::error::PakError;
+
+/// 打包时对符号链接的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// 跟随符号链接，打包链接指向的文件或目录（检测循环）
+    #[default]
+    Follow,
+    /// 跳过符号链接
+    Skip,
+    /// 遇到符号链接时报错
+    Error,
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "follow" => Ok(SymlinkPolicy::Follow),
+            "skip" => Ok(SymlinkPolicy::Skip),
+            "error" => Ok(SymlinkPolicy::Error),
+            _ => Err(format!("不支持的符号链接策略: {}（可选 follow、skip、error）", s)),
+        }
+    }
+}
+
+/// 目录的唯一标识，用于检测符号链接循环
+#[cfg(unix)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirId(u64, u64);
+
+/// 目录的唯一标识，用于检测符号链接循环
+#[cfg(not(unix))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirId(PathBuf);
+
+impl DirId {
+    /// 取得目录的标识（Unix 上为设备号和 inode，其他平台为规范化路径）
+    pub fn of(path: &Path) -> io::Result<DirId> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let metadata = fs::metadata(path).map_err(|e| unreadable(path, e))?;
+            Ok(DirId(metadata.dev(), metadata.ino()))
+        }
+        #[cfg(not(unix))]
+        {
+            Ok(DirId(fs::canonicalize(path).map_err(|e| unreadable(path, e))?))
+        }
+    }
+}
+
+/// 按符号链接策略解析后的目录项类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceEntry {
+    /// 可读的普通文件
+    File,
+    /// 目录（需要调用方检查循环后再进入）
+    Dir(DirId),
+    /// 按策略跳过的符号链接
+    SkippedSymlink,
+}
+
+/// 检查一个目录项：按策略处理符号链接，并拒绝特殊文件和无法读取的文件
+///
+/// 打包在写出任何数据之前调用，保证问题在创建输出文件前就被报告。
+pub fn classify_entry(path: &Path, policy: SymlinkPolicy) -> io::Result<SourceEntry> {
+    let link_metadata = fs::symlink_metadata(path).map_err(|e| unreadable(path, e))?;
+    let metadata = if link_metadata.file_type().is_symlink() {
+        match policy {
+            SymlinkPolicy::Skip => return Ok(SourceEntry::SkippedSymlink),
+            SymlinkPolicy::Error => return Err(PakError::SymlinkNotAllowed { path: path.to_path_buf() }.into()),
+            SymlinkPolicy::Follow => match fs::metadata(path) {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    return Err(PakError::BrokenSymlink { path: path.to_path_buf() }.into());
+                }
+                Err(e) => return Err(unreadable(path, e)),
+            },
+        }
+    } else {
+        link_metadata
+    };
+
+    if metadata.is_dir() {
+        return Ok(SourceEntry::Dir(DirId::of(path)?));
+    }
+    if !metadata.is_file() {
+        return Err(PakError::UnsupportedFileType { path: path.to_path_buf(), kind: special_file_kind(&metadata) }.into());
+    }
+
+    // 提前打开一次，确认有读取权限
+    fs::File::open(path).map_err(|e| unreadable(path, e))?;
+    Ok(SourceEntry::File)
+}
+
+/// 读取目录中的所有项，出错时带上目录路径
+pub fn read_dir_entries(dir: &Path) -> io::Result<Vec<fs::DirEntry>> {
+    fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| unreadable(dir, e))
+}
+
+fn unreadable(path: &Path, source: io::Error) -> io::Error {
+    PakError::UnreadableInput { path: path.to_path_buf(), source }.into()
+}
+
+/// 特殊文件的类型名称
+#[cfg(unix)]
+fn special_file_kind(metadata: &fs::Metadata) -> &'static str {
+    use std::os::unix::fs::FileTypeExt;
+    let file_type = metadata.file_type();
+    if file_type.is_socket() {
+        "套接字"
+    } else if file_type.is_fifo() {
+        "FIFO"
+    } else if file_type.is_block_device() {
+        "块设备"
+    } else if file_type.is_char_device() {
+        "字符设备"
+    } else {
+        "未知类型"
+    }
+}
+
+/// 特殊文件的类型名称
+#[cfg(not(unix))]
+fn special_file_kind(_metadata: &fs::Metadata) -> &'static str {
+    "未知类型"
+}
@@ -0,0 +1,420 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use crate::encoding::NameEncoding;
+use crate::error::PakError;
+use crate::pak::{write_pak_info, FileInfo, PakInfo};
+use crate::reader::normalize_entry_name;
+use crate::utils::{compress_data, system_time_to_filetime, write_file_atomically, CRYPT_KEY};
+
+/// 边写入边加密的包装器（对写入的每个字节异或密钥）
+pub struct CryptWriter<W> {
+    inner: W,
+    key: u8,
+    buf: Vec<u8>,
+}
+
+impl<W> CryptWriter<W> {
+    /// 创建包装器，key 为 0 时不做任何处理
+    pub fn new(inner: W, key: u8) -> Self {
+        Self { inner, key, buf: Vec::new() }
+    }
+
+    /// 取回内部写入器
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CryptWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.key == 0 {
+            return self.inner.write(data);
+        }
+
+        self.buf.clear();
+        self.buf.extend(data.iter().map(|byte| byte ^ self.key));
+        self.inner.write_all(&self.buf)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 待写入条目的数据来源
+enum EntrySource {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+    Reader { reader: Box<dyn Read>, size: u64 },
+}
+
+struct PendingEntry {
+    name: String,
+    source: EntrySource,
+    options: EntryOptions,
+}
+
+/// 单个条目的写入选项
+#[derive(Debug, Clone, Default)]
+pub struct EntryOptions {
+    /// 该条目的时间戳（Windows FILETIME），优先于写入器的设置
+    pub file_time: Option<u64>,
+    /// 原始文件名字节，解码后与条目名一致时原样写入
+    pub raw_name: Option<Vec<u8>>,
+}
+
+/// 打包进度回调（当前序号，总数）
+type ProgressFn = Box<dyn FnMut(usize, usize)>;
+
+/// 增量构建PAK文件的写入器
+///
+/// 条目可以来自内存、任意读取器或文件路径；输出时边写边加密，只需一遍。
+/// 压缩模式下需要先得到压缩后的大小才能写入头部，因此会预先在内存中压缩所有条目。
+pub struct PakWriter {
+    entries: Vec<PendingEntry>,
+    /// 已添加的条目名（按 `normalize_entry_name` 归一化，与读取时的查找规则一致）
+    names: HashSet<String>,
+    key: u8,
+    version: u32,
+    compress_level: Option<u32>,
+    file_time: Option<u64>,
+    encoding: NameEncoding,
+    progress: Option<ProgressFn>,
+}
+
+impl Default for PakWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PakWriter {
+    /// 创建写入器（默认加密、不压缩、文件条目使用文件的修改时间）
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            names: HashSet::new(),
+            key: CRYPT_KEY,
+            version: PakInfo::VERSION,
+            compress_level: None,
+            file_time: None,
+            encoding: NameEncoding::default(),
+            progress: None,
+        }
+    }
+
+    /// 设置是否加密输出（PC版PAK为加密格式）
+    pub fn encrypted(mut self, encrypted: bool) -> Self {
+        self.key = if encrypted { CRYPT_KEY } else { 0 };
+        self
+    }
+
+    /// 设置异或密钥，None 表示不加密
+    pub fn xor_key(mut self, key: Option<u8>) -> Self {
+        self.key = key.unwrap_or(0);
+        self
+    }
+
+    /// 设置头部中的版本号
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// 设置zlib压缩等级（0-9），None 表示不压缩
+    ///
+    /// 等级超出范围时，写入会返回 `PakError::InvalidCompressLevel`。
+    pub fn compression(mut self, level: Option<u32>) -> Self {
+        self.compress_level = level;
+        self
+    }
+
+    /// 设置所有条目使用的固定时间戳（Windows FILETIME）
+    ///
+    /// 不设置时，文件条目使用文件的修改时间，内存和读取器条目使用 [`PakInfo::DEFAULT_FILE_TIME`]。
+    pub fn file_time(mut self, file_time: u64) -> Self {
+        self.file_time = Some(file_time);
+        self
+    }
+
+    /// 设置文件名编码（默认GBK，Auto 时选择第一个能表示所有文件名的编码）
+    pub fn encoding(mut self, encoding: NameEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// 设置写入数据时的进度回调
+    pub fn on_progress(mut self, progress: impl FnMut(usize, usize) + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// 添加内存中的数据
+    pub fn add_bytes(&mut self, name: &str, data: Vec<u8>) -> io::Result<()> {
+        self.push(name, EntrySource::Bytes(data))
+    }
+
+    /// 添加读取器中的数据（需要预先给出数据大小）
+    pub fn add_reader(&mut self, name: &str, reader: impl Read + 'static, size: u64) -> io::Result<()> {
+        self.push(name, EntrySource::Reader { reader: Box::new(reader), size })
+    }
+
+    /// 添加文件（写入时才读取文件内容）
+    pub fn add_file(&mut self, name: &str, path: &Path) -> io::Result<()> {
+        self.push(name, EntrySource::File(path.to_path_buf()))
+    }
+
+    /// 添加文件，并指定时间戳、原始文件名等条目选项
+    ///
+    /// 指定了原始文件名字节时，条目名不做 `/` 到 `\` 的转换，以便与原始字节保持一致。
+    pub fn add_file_with_options(&mut self, name: &str, path: &Path, options: EntryOptions) -> io::Result<()> {
+        self.push_with_options(name, EntrySource::File(path.to_path_buf()), options)
+    }
+
+    /// 已添加的条目数量
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 是否没有任何条目
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 将PAK写入指定文件（已存在时替换）
+    ///
+    /// 先写入同一目录下的临时文件再改名，出错或中断时不会留下不完整的PAK。
+    pub fn write_to_path(self, path: &Path) -> io::Result<PakInfo> {
+        write_file_atomically(path, true, |writer| self.write_to(writer))
+    }
+
+    /// 将PAK写入新文件，目标已存在时返回 `PakError::OutputExists`
+    pub fn write_to_new_path(self, path: &Path) -> io::Result<PakInfo> {
+        if path.exists() {
+            return Err(PakError::OutputExists { path: path.to_path_buf() }.into());
+        }
+        write_file_atomically(path, false, |writer| self.write_to(writer))
+    }
+
+    /// 将PAK写入任意输出，返回写入的PAK信息
+    pub fn write_to<W: Write>(mut self, writer: W) -> io::Result<PakInfo> {
+        if let Some(level) = self.compress_level.filter(|level| *level > 9) {
+            return Err(PakError::InvalidCompressLevel { level }.into());
+        }
+        let mut writer = CryptWriter::new(writer, self.key);
+
+        // 时间戳需要在文件被读入内存之前取得
+        let file_times = self.entries.iter()
+            .map(|entry| match entry.options.file_time {
+                Some(file_time) => Ok(file_time),
+                None => entry_file_time(&entry.source, self.file_time),
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        // 压缩模式下先把所有条目压缩为内存数据，并记录原始大小
+        let mut original_sizes = Vec::new();
+        if let Some(level) = self.compress_level {
+            for entry in &mut self.entries {
+                let data = read_source(&mut entry.source, &entry.name)?;
+                original_sizes.push(checked_size(data.len() as u64, &entry.name)?);
+                entry.source = EntrySource::Bytes(compress_data(&data, level)?);
+            }
+        }
+
+        // 构建文件信息
+        let mut pak_info = PakInfo::new();
+        pak_info.version = self.version;
+        pak_info.compress = Some(self.compress_level.is_some());
+        pak_info.encoding = self.encoding;
+        for (index, entry) in self.entries.iter().enumerate() {
+            let z_size = checked_size(source_size(&entry.source)?, &entry.name)?;
+            let size = if self.compress_level.is_some() {
+                original_sizes[index]
+            } else {
+                z_size
+            };
+            pak_info.file_info_library.push(FileInfo {
+                file_name: entry.name.clone(),
+                z_size,
+                _size: size,
+                _file_time: file_times[index],
+                raw_name: entry.options.raw_name.clone(),
+            });
+        }
+
+        write_pak_info(&mut writer, &pak_info)?;
+
+        // 写入文件数据
+        let total = self.entries.len();
+        for (index, (entry, file_info)) in self.entries.into_iter().zip(&pak_info.file_info_library).enumerate() {
+            if let Some(progress) = self.progress.as_mut() {
+                progress(index, total);
+            }
+            write_source(&mut writer, entry.source, file_info)?;
+        }
+
+        writer.flush()?;
+        Ok(pak_info)
+    }
+
+    fn push(&mut self, name: &str, source: EntrySource) -> io::Result<()> {
+        self.push_with_options(name, source, EntryOptions::default())
+    }
+
+    fn push_with_options(&mut self, name: &str, source: EntrySource, options: EntryOptions) -> io::Result<()> {
+        // PAK中统一使用Windows风格路径（保留原始文件名时除外）
+        let name = if options.raw_name.is_some() {
+            name.to_string()
+        } else {
+            name.replace('/', "\\")
+        };
+        // 超过上限的索引在读取时会被拒绝
+        if self.entries.len() >= PakInfo::MAX_ENTRIES {
+            return Err(PakError::TooManyEntries { limit: PakInfo::MAX_ENTRIES }.into());
+        }
+        // 只有大小写或分隔符不同的条目名在读取时无法区分，同样视为重复
+        if !self.names.insert(normalize_entry_name(&name)) {
+            return Err(PakError::DuplicateName { name }.into());
+        }
+        self.entries.push(PendingEntry { name, source, options });
+        Ok(())
+    }
+}
+
+/// 读取数据来源的全部内容
+fn read_source(source: &mut EntrySource, name: &str) -> io::Result<Vec<u8>> {
+    match source {
+        EntrySource::Bytes(data) => Ok(std::mem::take(data)),
+        EntrySource::File(path) => fs::read(path),
+        EntrySource::Reader { reader, size } => {
+            let mut data = Vec::new();
+            copy_exact(reader, &mut data, *size, name)?;
+            Ok(data)
+        }
+    }
+}
+
+/// 数据来源的大小
+fn source_size(source: &EntrySource) -> io::Result<u64> {
+    match source {
+        EntrySource::Bytes(data) => Ok(data.len() as u64),
+        EntrySource::File(path) => Ok(fs::metadata(path)?.len()),
+        EntrySource::Reader { size, .. } => Ok(*size),
+    }
+}
+
+/// 条目的时间戳：优先使用固定时间，否则取文件的修改时间
+fn entry_file_time(source: &EntrySource, fixed: Option<u64>) -> io::Result<u64> {
+    if let Some(file_time) = fixed {
+        return Ok(file_time);
+    }
+    match source {
+        EntrySource::File(path) => {
+            let modified = fs::metadata(path)?.modified()?;
+            Ok(system_time_to_filetime(modified))
+        }
+        _ => Ok(PakInfo::DEFAULT_FILE_TIME),
+    }
+}
+
+/// 将数据来源写入输出，并校验写入的字节数与头部记录一致
+fn write_source<W: Write>(writer: &mut W, source: EntrySource, file_info: &FileInfo) -> io::Result<()> {
+    let expected = file_info.z_size as u64;
+    match source {
+        EntrySource::Bytes(data) => writer.write_all(&data),
+        EntrySource::File(path) => {
+            let mut file = File::open(&path)?;
+            copy_exact(&mut file, writer, expected, &file_info.file_name)
+        }
+        EntrySource::Reader { mut reader, .. } => {
+            copy_exact(&mut reader, writer, expected, &file_info.file_name)
+        }
+    }
+}
+
+/// 从来源复制恰好 expected 字节，来源变短或变长时都返回 `PakError::SourceChanged`
+fn copy_exact<R: Read + ?Sized, W: Write + ?Sized>(reader: &mut R, writer: &mut W, expected: u64, name: &str) -> io::Result<()> {
+    let mut copied = io::copy(&mut reader.take(expected), writer)?;
+    if copied == expected {
+        // 头部已经按原来的大小写出，多出的数据只统计长度，不写入
+        copied += io::copy(reader, &mut io::sink())?;
+    }
+    if copied != expected {
+        return Err(PakError::SourceChanged {
+            name: name.to_string(),
+            expected,
+            actual: copied,
+        }.into());
+    }
+    Ok(())
+}
+
+/// 检查大小是否能用u32表示（PAK中的大小字段为32位）
+pub(crate) fn checked_size(size: u64, name: &str) -> io::Result<u32> {
+    u32::try_from(size).map_err(|_| {
+        PakError::EntryTooLarge { name: name.to_string(), size }.into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::PakReader;
+
+    fn source_changed(error: &io::Error) -> Option<(u64, u64)> {
+        match PakError::from_io(error) {
+            Some(PakError::SourceChanged { expected, actual, .. }) => Some((*expected, *actual)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn written_pak_reads_back_with_every_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("c.bin");
+        fs::write(&file_path, [7u8; 3000]).unwrap();
+
+        for compression in [None, Some(0), Some(9)] {
+            for encrypted in [false, true] {
+                let mut writer = PakWriter::new().compression(compression).encrypted(encrypted).file_time(42);
+                writer.add_bytes("a.txt", b"hello".to_vec()).unwrap();
+                writer.add_bytes("dir\\empty.txt", Vec::new()).unwrap();
+                writer.add_file("dir/c.bin", &file_path).unwrap();
+                let mut bytes = Vec::new();
+                writer.write_to(&mut bytes).unwrap();
+
+                let mut reader = PakReader::new(io::Cursor::new(bytes)).unwrap();
+                let layout = (compression, encrypted);
+                assert_eq!(reader.is_compressed(), compression.is_some(), "{:?}", layout);
+                assert_eq!(reader.variant().xor_key.is_some(), encrypted, "{:?}", layout);
+                let names: Vec<_> = reader.files().iter().map(|file_info| file_info.file_name.as_str()).collect();
+                assert_eq!(names, ["a.txt", "dir\\empty.txt", "dir\\c.bin"], "{:?}", layout);
+                assert!(reader.files().iter().all(|file_info| file_info._file_time == 42), "{:?}", layout);
+                assert_eq!(reader.read_entry(0).unwrap(), b"hello", "{:?}", layout);
+                assert!(reader.read_entry(1).unwrap().is_empty(), "{:?}", layout);
+                assert_eq!(reader.read_entry(2).unwrap(), [7u8; 3000], "{:?}", layout);
+            }
+        }
+    }
+
+    #[test]
+    fn reader_longer_than_declared_size_is_rejected() {
+        let mut writer = PakWriter::new();
+        writer.add_reader("a.txt", io::Cursor::new(b"grown data".to_vec()), 5).unwrap();
+
+        let error = writer.write_to(Vec::new()).unwrap_err();
+        assert_eq!(source_changed(&error), Some((5, 10)));
+    }
+
+    #[test]
+    fn reader_shorter_than_declared_size_is_rejected() {
+        let mut writer = PakWriter::new().compression(Some(6));
+        writer.add_reader("a.txt", io::Cursor::new(b"abc".to_vec()), 5).unwrap();
+
+        let error = writer.write_to(Vec::new()).unwrap_err();
+        assert_eq!(source_changed(&error), Some((5, 3)));
+    }
+}